@@ -0,0 +1,84 @@
+//! Compares the cost of the facade read path against the previous `RwLock<Arc<Vfs>>` design
+//!
+//! Run with `cargo bench --bench read_path`
+use std::{
+    hint::black_box,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rivia_vfs::prelude::*;
+
+const ITERATIONS: u32 = 1_000_000;
+const THREADS: u32 = 4;
+
+fn main() {
+    assert!(vfs::set_memfs().is_ok());
+    let file = vfs::root().mash("file");
+    assert!(vfs::mkfile(&file).is_ok());
+
+    // Previous design: take the read lock and clone the Arc on every call
+    let locked: Arc<RwLock<Arc<Vfs>>> = Arc::new(RwLock::new(vfs::provider()));
+
+    println!("{:<28}{:>12}{:>12}", "", "1 thread", format!("{} threads", THREADS));
+
+    // Isolate the cost of acquiring the provider from the cost of the operation itself
+    let legacy = {
+        let locked = locked.clone();
+        bench(move || matches!(*locked.read().unwrap().clone(), Vfs::Memfs(_)))
+    };
+    report("RwLock<Arc<Vfs>> provider", legacy);
+    report("vfs::is_memfs", bench(vfs::is_memfs));
+
+    for (name, op) in [("exists", false), ("is_file", true)] {
+        let legacy = {
+            let locked = locked.clone();
+            let file = file.clone();
+            bench(move || {
+                let vfs = locked.read().unwrap().clone();
+                if op {
+                    vfs.is_file(&file)
+                } else {
+                    vfs.exists(&file)
+                }
+            })
+        };
+        let facade = {
+            let file = file.clone();
+            bench(move || if op { vfs::is_file(&file) } else { vfs::exists(&file) })
+        };
+        report(&format!("RwLock<Arc<Vfs>> {}", name), legacy);
+        report(&format!("vfs::{}", name), facade);
+    }
+}
+
+// Run the given operation in a tight loop returning the average ns per call for a single thread
+// and when contended by multiple threads.
+fn bench<F: Fn() -> bool + Clone + Send + 'static>(f: F) -> (f64, f64) {
+    let single = run(f.clone());
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let f = f.clone();
+            thread::spawn(move || run(f))
+        })
+        .collect();
+    let contended = handles.into_iter().map(|x| x.join().unwrap()).sum::<Duration>() / THREADS;
+    (per_call(single), per_call(contended))
+}
+
+fn run<F: Fn() -> bool>(f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+fn per_call(elapsed: Duration) -> f64 {
+    elapsed.as_nanos() as f64 / ITERATIONS as f64
+}
+
+fn report(name: &str, (single, contended): (f64, f64)) {
+    println!("{:<28}{:>9.1} ns{:>9.1} ns", name, single, contended);
+}
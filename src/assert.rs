@@ -1,6 +1,38 @@
+//! Assertion macros operating against the current vfs provider
+//!
+//! Each assertion may optionally be prefixed with `@name` to target a named provider registered
+//! via `vfs::register` rather than the current provider. Paths below a mount point added via
+//! `vfs::mount` are checked against the mounted provider.
+//!
+//! ### Examples
+//! ```
+//! use rivia_vfs::prelude::*;
+//!
+//! assert!(vfs::register("scratch", Vfs::memfs()).is_ok());
+//! let tmpdir = assert_setup!(@"scratch", "unique_func_name");
+//! let file = tmpdir.mash("file");
+//! assert_mkfile!(@"scratch", &file);
+//! assert_is_file!(@"scratch", &file);
+//! assert_no_file!(&file);
+//! ```
 #[allow(unused_imports)]
 use super::prelude::*;
 
+/// Resolve the named provider for the `@name` form of the assertion macros
+///
+/// Panics naming the given assertion macro and provider name when no provider has been registered
+/// with the given name.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! assert_named {
+    ($macro:expr, $name:expr) => {
+        match vfs::named($name) {
+            Ok(x) => x,
+            _ => panic_msg!($macro, "named provider isn't registered", $name),
+        }
+    };
+}
+
 /// Setup Vfs testing components using the current provider is
 ///
 /// This provides an abstraction over VirtualFileSystem implementations such that we can easily
@@ -23,15 +55,28 @@ use super::prelude::*;
 ///
 /// let tmpdir = assert_setup!("unique_func_name");
 /// assert_remove_all!(&tmpdir);
+///
+/// // Setup against a named provider instead
+/// assert!(vfs::register("scratch", Vfs::memfs()).is_ok());
+/// let tmpdir = assert_setup!(@"scratch", "unique_func_name");
+/// assert_remove_all!(@"scratch", &tmpdir);
 /// ```
 #[macro_export]
 macro_rules! assert_setup {
+    (@$name:expr, $func:expr) => {{
+        let (_, tmpdir) = assert_vfs_setup!($crate::assert_named!("assert_setup!", $name), $func);
+        tmpdir
+    }};
+    (@$name:expr) => {{
+        let (_, tmpdir) = assert_vfs_setup!($crate::assert_named!("assert_setup!", $name));
+        tmpdir
+    }};
     ($func:expr) => {{
-        let (_, tmpdir) = assert_vfs_setup!(vfs::VFS.read().unwrap().clone(), $func);
+        let (_, tmpdir) = assert_vfs_setup!(vfs::provider(), $func);
         tmpdir
     }};
     () => {{
-        let (_, tmpdir) = assert_vfs_setup!(vfs::VFS.read().unwrap().clone());
+        let (_, tmpdir) = assert_vfs_setup!(vfs::provider());
         tmpdir
     }};
 }
@@ -44,6 +89,8 @@ macro_rules! assert_setup {
 /// directory inside that using the derived fully qualified function name or given function name
 /// when it can't be derived.
 ///
+/// * Switches the thread local override to Memfs when one has been set via `vfs::set_local`
+///
 /// ### Warning
 /// Since doc tests always have a default function name of `rust_out::main` its required to override
 /// the `func_name` param to get a unique directory to work with in the Stdfs case as you won't get
@@ -63,12 +110,12 @@ macro_rules! assert_setup {
 macro_rules! assert_memfs_setup {
     ($func:expr) => {{
         assert!(vfs::set_memfs().is_ok());
-        let (_, tmpdir) = assert_vfs_setup!(vfs::VFS.read().unwrap().clone(), $func);
+        let (_, tmpdir) = assert_vfs_setup!(vfs::provider(), $func);
         tmpdir
     }};
     () => {{
         assert!(vfs::set_memfs().is_ok());
-        let (_, tmpdir) = assert_vfs_setup!(vfs::VFS.read().unwrap().clone());
+        let (_, tmpdir) = assert_vfs_setup!(vfs::provider());
         tmpdir
     }};
 }
@@ -81,6 +128,8 @@ macro_rules! assert_memfs_setup {
 /// directory inside that using the derived fully qualified function name or given function name
 /// when it can't be derived.
 ///
+/// * Switches the thread local override to Stdfs when one has been set via `vfs::set_local`
+///
 /// ### Warning
 /// Since doc tests always have a default function name of `rust_out::main` its required to override
 /// the `func_name` param to get a unique directory to work with in the Stdfs case as you won't get
@@ -100,16 +149,82 @@ macro_rules! assert_memfs_setup {
 macro_rules! assert_stdfs_setup {
     ($func:expr) => {{
         assert!(vfs::set_stdfs().is_ok());
-        let (_, tmpdir) = assert_vfs_setup!(vfs::VFS.read().unwrap().clone(), $func);
+        let (_, tmpdir) = assert_vfs_setup!(vfs::provider(), $func);
         tmpdir
     }};
     () => {{
         assert!(vfs::set_stdfs().is_ok());
-        let (_, tmpdir) = assert_vfs_setup!(vfs::VFS.read().unwrap().clone());
+        let (_, tmpdir) = assert_vfs_setup!(vfs::provider());
         tmpdir
     }};
 }
 
+/// Assert that the given facade function was called while recording via `vfs::set_recording`
+///
+/// * Optionally asserts that one of the calls was given the path
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::set_recording(true).is_ok());
+/// assert!(vfs::write_all("foo", "bar").is_ok());
+/// assert_called!(write_all);
+/// assert_called!(write_all, "foo");
+/// ```
+#[macro_export]
+macro_rules! assert_called {
+    ($op:ident, $path:expr) => {{
+        let path = $path;
+        let target = match vfs::provider().abs(&path) {
+            Ok(x) => x,
+            _ => panic_msg!("assert_called!", "failed to get absolute path", &path),
+        };
+        if !vfs::calls().iter().any(|x| x.op == stringify!($op) && x.args.contains(&target)) {
+            panic_msg!("assert_called!", format!("{} wasn't called with path", stringify!($op)), &target);
+        }
+    }};
+    ($op:ident) => {
+        if !vfs::calls().iter().any(|x| x.op == stringify!($op)) {
+            panic_msg!("assert_called!", "function wasn't called", stringify!($op));
+        }
+    };
+}
+
+/// Assert that the given facade function wasn't called while recording via `vfs::set_recording`
+///
+/// * Optionally only asserts that none of the calls were given the path
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::set_recording(true).is_ok());
+/// assert!(vfs::write_all("foo", "bar").is_ok());
+/// assert_not_called!(remove_all);
+/// assert_not_called!(write_all, "bar");
+/// ```
+#[macro_export]
+macro_rules! assert_not_called {
+    ($op:ident, $path:expr) => {{
+        let path = $path;
+        let target = match vfs::provider().abs(&path) {
+            Ok(x) => x,
+            _ => panic_msg!("assert_not_called!", "failed to get absolute path", &path),
+        };
+        if vfs::calls().iter().any(|x| x.op == stringify!($op) && x.args.contains(&target)) {
+            panic_msg!("assert_not_called!", format!("{} was called with path", stringify!($op)), &target);
+        }
+    }};
+    ($op:ident) => {
+        if vfs::calls().iter().any(|x| x.op == stringify!($op)) {
+            panic_msg!("assert_not_called!", "function was called", stringify!($op));
+        }
+    };
+}
+
 /// Assert the copy of a file
 ///
 /// ### Examples
@@ -124,8 +239,12 @@ macro_rules! assert_stdfs_setup {
 /// ```
 #[macro_export]
 macro_rules! assert_copyfile {
+    (@$name:expr, $from:expr, $to:expr) => {{
+        let named = $crate::assert_named!("assert_copyfile!", $name);
+        assert_vfs_copyfile!(named, $from, $to);
+    }};
     ($from:expr, $to:expr) => {
-        assert_vfs_copyfile!(vfs::VFS.read().unwrap().clone(), $from, $to)
+        assert_vfs_copyfile!(vfs::provider_at($from), $from, $to)
     };
 }
 
@@ -140,8 +259,12 @@ macro_rules! assert_copyfile {
 /// ```
 #[macro_export]
 macro_rules! assert_exists {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_exists!", $name);
+        assert_vfs_exists!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_exists!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_exists!(vfs::provider_at($path), $path)
     };
 }
 
@@ -156,8 +279,12 @@ macro_rules! assert_exists {
 /// ```
 #[macro_export]
 macro_rules! assert_no_exists {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_no_exists!", $name);
+        assert_vfs_no_exists!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_no_exists!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_no_exists!(vfs::provider_at($path), $path)
     };
 }
 
@@ -174,8 +301,12 @@ macro_rules! assert_no_exists {
 /// ```
 #[macro_export]
 macro_rules! assert_is_dir {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_is_dir!", $name);
+        assert_vfs_is_dir!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_is_dir!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_is_dir!(vfs::provider_at($path), $path)
     };
 }
 
@@ -192,8 +323,12 @@ macro_rules! assert_is_dir {
 /// ```
 #[macro_export]
 macro_rules! assert_no_dir {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_no_dir!", $name);
+        assert_vfs_no_dir!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_no_dir!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_no_dir!(vfs::provider_at($path), $path)
     };
 }
 
@@ -210,8 +345,12 @@ macro_rules! assert_no_dir {
 /// ```
 #[macro_export]
 macro_rules! assert_is_file {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_is_file!", $name);
+        assert_vfs_is_file!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_is_file!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_is_file!(vfs::provider_at($path), $path)
     };
 }
 
@@ -226,8 +365,12 @@ macro_rules! assert_is_file {
 /// ```
 #[macro_export]
 macro_rules! assert_no_file {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_no_file!", $name);
+        assert_vfs_no_file!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_no_file!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_no_file!(vfs::provider_at($path), $path)
     };
 }
 
@@ -244,8 +387,12 @@ macro_rules! assert_no_file {
 /// ```
 #[macro_export]
 macro_rules! assert_is_symlink {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_is_symlink!", $name);
+        assert_vfs_is_symlink!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_is_symlink!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_is_symlink!(vfs::provider_at($path), $path)
     };
 }
 
@@ -260,8 +407,12 @@ macro_rules! assert_is_symlink {
 /// ```
 #[macro_export]
 macro_rules! assert_no_symlink {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_no_symlink!", $name);
+        assert_vfs_no_symlink!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_no_symlink!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_no_symlink!(vfs::provider_at($path), $path)
     };
 }
 
@@ -278,8 +429,12 @@ macro_rules! assert_no_symlink {
 /// ```
 #[macro_export]
 macro_rules! assert_mkdir_m {
+    (@$name:expr, $path:expr, $mode:expr) => {{
+        let named = $crate::assert_named!("assert_mkdir_m!", $name);
+        assert_vfs_mkdir_m!(named, $path, $mode);
+    }};
     ($path:expr, $mode:expr) => {
-        assert_vfs_mkdir_m!(vfs::VFS.read().unwrap().clone(), $path, $mode)
+        assert_vfs_mkdir_m!(vfs::provider_at($path), $path, $mode)
     };
 }
 
@@ -296,8 +451,12 @@ macro_rules! assert_mkdir_m {
 /// ```
 #[macro_export]
 macro_rules! assert_mkdir_p {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_mkdir_p!", $name);
+        assert_vfs_mkdir_p!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_mkdir_p!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_mkdir_p!(vfs::provider_at($path), $path)
     };
 }
 
@@ -314,8 +473,12 @@ macro_rules! assert_mkdir_p {
 /// ```
 #[macro_export]
 macro_rules! assert_mkfile {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_mkfile!", $name);
+        assert_vfs_mkfile!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_mkfile!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_mkfile!(vfs::provider_at($path), $path)
     };
 }
 
@@ -332,8 +495,12 @@ macro_rules! assert_mkfile {
 /// ```
 #[macro_export]
 macro_rules! assert_read_all {
+    (@$name:expr, $path:expr, $data:expr) => {{
+        let named = $crate::assert_named!("assert_read_all!", $name);
+        assert_vfs_read_all!(named, $path, $data);
+    }};
     ($path:expr, $data:expr) => {
-        assert_vfs_read_all!(vfs::VFS.read().unwrap().clone(), $path, $data)
+        assert_vfs_read_all!(vfs::provider_at($path), $path, $data)
     };
 }
 
@@ -350,8 +517,12 @@ macro_rules! assert_read_all {
 /// ```
 #[macro_export]
 macro_rules! assert_readlink {
+    (@$name:expr, $path:expr, $target:expr) => {{
+        let named = $crate::assert_named!("assert_readlink!", $name);
+        assert_vfs_readlink!(named, $path, $target);
+    }};
     ($path:expr, $target:expr) => {
-        assert_vfs_readlink!(vfs::VFS.read().unwrap().clone(), $path, $target)
+        assert_vfs_readlink!(vfs::provider_at($path), $path, $target)
     };
 }
 
@@ -368,8 +539,12 @@ macro_rules! assert_readlink {
 /// ```
 #[macro_export]
 macro_rules! assert_readlink_abs {
+    (@$name:expr, $path:expr, $data:expr) => {{
+        let named = $crate::assert_named!("assert_readlink_abs!", $name);
+        assert_vfs_readlink_abs!(named, $path, $data);
+    }};
     ($path:expr, $data:expr) => {
-        assert_vfs_readlink_abs!(vfs::VFS.read().unwrap().clone(), $path, $data)
+        assert_vfs_readlink_abs!(vfs::provider_at($path), $path, $data)
     };
 }
 
@@ -391,8 +566,12 @@ macro_rules! assert_readlink_abs {
 /// ```
 #[macro_export]
 macro_rules! assert_remove {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_remove!", $name);
+        assert_vfs_remove!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_remove!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_remove!(vfs::provider_at($path), $path)
     };
 }
 
@@ -414,8 +593,12 @@ macro_rules! assert_remove {
 /// ```
 #[macro_export]
 macro_rules! assert_remove_all {
+    (@$name:expr, $path:expr) => {{
+        let named = $crate::assert_named!("assert_remove_all!", $name);
+        assert_vfs_remove_all!(named, $path);
+    }};
     ($path:expr) => {
-        assert_vfs_remove_all!(vfs::VFS.read().unwrap().clone(), $path)
+        assert_vfs_remove_all!(vfs::provider_at($path), $path)
     };
 }
 
@@ -432,8 +615,12 @@ macro_rules! assert_remove_all {
 /// ```
 #[macro_export]
 macro_rules! assert_symlink {
+    (@$name:expr, $link:expr, $target:expr) => {{
+        let named = $crate::assert_named!("assert_symlink!", $name);
+        assert_vfs_symlink!(named, $link, $target);
+    }};
     ($link:expr, $target:expr) => {
-        assert_vfs_symlink!(vfs::VFS.read().unwrap().clone(), $link, $target)
+        assert_vfs_symlink!(vfs::provider_at($link), $link, $target)
     };
 }
 
@@ -450,8 +637,12 @@ macro_rules! assert_symlink {
 /// ```
 #[macro_export]
 macro_rules! assert_write_all {
+    (@$name:expr, $path:expr, $data:expr) => {{
+        let named = $crate::assert_named!("assert_write_all!", $name);
+        assert_vfs_write_all!(named, $path, $data);
+    }};
     ($path:expr, $data:expr) => {
-        assert_vfs_write_all!(vfs::VFS.read().unwrap().clone(), $path, $data)
+        assert_vfs_write_all!(vfs::provider_at($path), $path, $data)
     };
 }
 
@@ -465,7 +656,7 @@ mod tests
     #[allow(dead_code)]
     fn dump_memfs()
     {
-        if let Vfs::Memfs(ref x) = **vfs::VFS.read().unwrap() {
+        if let Vfs::Memfs(ref x) = *vfs::provider() {
             println!("{}", x);
         }
     }
@@ -473,23 +664,83 @@ mod tests
     #[test]
     fn test_assert_setup()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let expected =
             vfs::root().mash(testing::TEST_TEMP_DIR).mash("rivia_vfs::assert::tests::test_assert_setup");
         assert_eq!(&tmpdir, &expected);
         assert_exists!(&expected);
 
         // Try with a function name override
-        let tmpdir = assert_memfs_setup!("foobar_setup");
+        let tmpdir = assert_setup!("foobar_setup");
         let expected = vfs::root().mash(testing::TEST_TEMP_DIR).mash("foobar_setup");
         assert_eq!(&tmpdir, &expected);
         assert_exists!(&expected);
     }
 
     #[test]
-    fn test_assert_copyfile()
+    fn test_assert_memfs_setup()
     {
+        // Switches the thread local provider rather than touching the real filesystem
+        assert!(vfs::set_local(Vfs::stdfs()).is_ok());
         let tmpdir = assert_memfs_setup!();
+        assert!(vfs::is_memfs());
+        assert_exists!(&tmpdir);
+        assert!(!Vfs::stdfs().exists(&tmpdir));
+    }
+
+    #[test]
+    fn test_assert_named()
+    {
+        let result = std::panic::catch_unwind(|| {
+            assert_exists!(@"test_assert_named", "foo");
+        });
+        let err = result.unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("assert_exists!: named provider isn't registered"));
+        assert!(msg.contains("test_assert_named"));
+    }
+
+    #[test]
+    fn test_assert_called_not_called()
+    {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let file1 = tmpdir.mash("file1");
+        let file2 = tmpdir.mash("file2");
+        assert!(vfs::set_recording(true).is_ok());
+        assert!(vfs::write_all(&file1, "foobar").is_ok());
+        assert_called!(write_all);
+        assert_called!(write_all, &file1);
+        assert_not_called!(write_all, &file2);
+        assert_not_called!(remove_all);
+
+        // Assertions aren't recorded themselves
+        assert_write_all!(&file2, "foobar");
+        assert_not_called!(write_all, &file2);
+        assert_eq!(vfs::calls().len(), 1);
+
+        let result = std::panic::catch_unwind(|| {
+            assert_called!(remove_all);
+        });
+        let err = result.unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("assert_called!: function wasn't called"));
+        assert!(msg.contains("remove_all"));
+
+        let result = std::panic::catch_unwind(|| {
+            assert_not_called!(write_all, &file1);
+        });
+        let err = result.unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("assert_not_called!: write_all was called with path"));
+    }
+
+    #[test]
+    fn test_assert_copyfile()
+    {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         let file2 = tmpdir.mash("file2");
         assert_write_all!(&file1, "this is a test");
@@ -499,7 +750,8 @@ mod tests
     #[test]
     fn test_assert_exists_and_no_exists()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
 
         // Test file exists
         {
@@ -525,7 +777,8 @@ mod tests
     #[test]
     fn test_assert_is_dir_no_dir()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         assert_no_dir!(&dir1);
         assert_mkdir_p!(&dir1);
@@ -535,7 +788,8 @@ mod tests
     #[test]
     fn test_assert_is_file_no_file()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         assert_no_file!(&file1);
         assert_mkfile!(&file1);
@@ -545,7 +799,8 @@ mod tests
     #[test]
     fn test_assert_is_symlink_no_symlink()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         let link1 = tmpdir.mash("link1");
 
@@ -562,7 +817,8 @@ mod tests
     #[test]
     fn test_assert_mkdir_m()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         assert_no_dir!(&dir1);
         assert_mkdir_m!(&dir1, 0o40777);
@@ -573,7 +829,8 @@ mod tests
     #[test]
     fn test_assert_mkdir_p()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         assert_no_dir!(&dir1);
         assert_mkdir_p!(&dir1);
@@ -583,7 +840,8 @@ mod tests
     #[test]
     fn test_assert_mkfile()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         assert_no_file!(&file1);
         assert_mkfile!(&file1);
@@ -593,7 +851,8 @@ mod tests
     #[test]
     fn test_assert_read_all()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("foo");
         assert_write_all!(&file, b"foobar 1");
         assert_read_all!(&file, "foobar 1".to_string());
@@ -602,7 +861,8 @@ mod tests
     #[test]
     fn test_assert_readlink()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let link = dir.mash("link");
         let file = tmpdir.mash("file");
@@ -618,7 +878,8 @@ mod tests
     #[test]
     fn test_assert_readlink_abs()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let link = dir.mash("link");
         let file = tmpdir.mash("file");
@@ -634,7 +895,8 @@ mod tests
     #[test]
     fn test_assert_remove()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         assert_remove!(&file1);
         assert_mkfile!(&file1);
@@ -646,7 +908,8 @@ mod tests
     #[test]
     fn test_assert_remove_all()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         assert_mkfile!(&file1);
         assert_is_file!(&file1);
@@ -657,7 +920,8 @@ mod tests
     #[test]
     fn test_assert_symlink()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         let file1 = dir1.mash("file1");
         let link1 = tmpdir.mash("link1");
@@ -672,7 +936,8 @@ mod tests
     #[test]
     fn test_assert_write_all()
     {
-        let tmpdir = assert_memfs_setup!();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("foo");
         assert_write_all!(&file, b"foobar 1");
         assert_read_all!(&file, "foobar 1".to_string());
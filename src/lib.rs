@@ -10,6 +10,13 @@
 //! however you change the backend provider by simply calling the `vfs::set()` function and pass in
 //! a different variant of the [`Vfs`] enum.
 //!
+//! Alternatively `vfs::set_local()` will override the provider for the current thread only, which
+//! allows parallel tests to each work against their own isolated Memfs.
+//!
+//! The initial provider can also be chosen without recompiling by setting the `RIVIA_VFS`
+//! environment variable to a provider URI e.g. `RIVIA_VFS=memfs://`. See [`from_uri`] for the
+//! supported URIs. Invalid values fall back to Stdfs, call [`init_from_env`] to get the error.
+//!
 //! ### Example
 //! ```
 //! use rivia_vfs::prelude::*;
@@ -42,110 +49,1809 @@
 //! ```
 #[macro_use]
 pub mod assert;
-use std::sync::{Arc, RwLock};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    ffi::OsStr,
+    fmt, io,
+    ops::Deref,
+    os::unix::ffi::OsStrExt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+use lazy_static::lazy_static;
+use rivia::prelude::*;
+
+/// All essential symbols in a simple consumable form
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+/// ```
+pub mod prelude {
+    pub use rivia::prelude::*;
+
+    // Export macros by name
+    pub use crate::{
+        assert_called, assert_copyfile, assert_exists, assert_is_dir, assert_is_file, assert_is_symlink,
+        assert_memfs_setup, assert_mkdir_m, assert_mkdir_p, assert_mkfile, assert_no_dir, assert_no_exists,
+        assert_no_file, assert_no_symlink, assert_not_called, assert_read_all, assert_readlink,
+        assert_readlink_abs, assert_remove, assert_remove_all, assert_setup, assert_stdfs_setup, assert_symlink,
+        assert_write_all,
+    };
+
+    // Nest global vfs functions for ergonomics
+    pub mod vfs {
+        pub use crate::*;
+    }
+}
+
+/// Environment variable used to select the initial vfs provider via a provider URI
+///
+/// * Invalid values fall back to Stdfs, use `init_from_env` to get the error
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// // Only takes effect when set before the vfs is first used
+/// std::env::set_var(vfs::VFS_ENV, "memfs://");
+/// assert!(vfs::is_memfs());
+/// ```
+pub const VFS_ENV: &str = "RIVIA_VFS";
+
+lazy_static! {
+    /// VFS is a virtual filesystem singleton providing an implementation of Vfs that defaults to
+    /// Stdfs but can be changed dynamically to any variant of the Vfs enum.
+    ///
+    /// Arc is used here to provide the guarantee that the shared VFS instance is thread safe and
+    /// is held in an ArcSwap to provide the ability to change the VFS backend implementation if
+    /// desired following the promoting pattern rather than interior mutability i.e. Arc<RwLock>>.
+    /// Since changing the backend will be a rare occurance while reading it happens on every call
+    /// ArcSwap is used rather than a RwLock as reads are lock free, don't need to touch the Arc's
+    /// reference count and can never be poisoned by a panicking writer.
+    /// https://blog.sentry.io/2018/04/05/you-cant-rust-that
+    ///
+    /// The initial provider is parsed from the `RIVIA_VFS` environment variable when it is set
+    /// falling back to Stdfs if the value isn't a supported provider URI. Panicking here instead
+    /// would poison every later use of the facade.
+    pub static ref VFS: ArcSwap<Vfs> = ArcSwap::from_pointee(
+        std::env::var(VFS_ENV).ok().and_then(|uri| from_uri(uri).ok()).unwrap_or_else(Vfs::stdfs)
+    );
+
+    /// REGISTRY holds the named vfs providers managed via `register` and `unregister`. Lookups
+    /// are lock free while changes swap in an updated copy of the map.
+    static ref REGISTRY: ArcSwap<HashMap<String, Arc<Vfs>>> = ArcSwap::default();
+
+    /// MOUNTS holds the providers attached at path prefixes via `mount` and `umount` ordered with
+    /// the deepest mount points first along with the working directory when it is in one of them.
+    /// Lookups are lock free while changes swap in an updated copy.
+    static ref MOUNTS: ArcSwap<Mounts> = ArcSwap::default();
+}
+
+/// HOOKS holds the settings applied to facade calls before they reach a provider e.g. the read-only
+/// mode set via `set_readonly`, the call log kept by `set_recording` and the failures added via
+/// `inject` along with the virtual clock advanced by `add_latency`. Since they are rarely active
+/// HOOKED mirrors whether any are, which keeps the common case down to a single atomic load rather
+/// than taking the lock on every call.
+static HOOKS: Mutex<Hooks> = Mutex::new(Hooks::new());
+static HOOKED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// LOCAL is an optional per thread override of the global VFS singleton. When set all facade
+    /// functions called from the owning thread will use it rather than the global VFS which allows
+    /// for isolated providers per thread e.g. a separate Memfs for each parallel test.
+    static LOCAL: RefCell<Option<Arc<Vfs>>> = const { RefCell::new(None) };
+
+    /// LOCAL_MOUNTS holds the mounts made from the owning thread while LOCAL is set. They are used
+    /// in place of the global MOUNTS so that mounting keeps each thread's provider isolated.
+    static LOCAL_MOUNTS: RefCell<Mounts> = const { RefCell::new(Mounts::new()) };
+
+    /// LOCAL_HOOKS holds the facade call settings made from the owning thread while LOCAL is set
+    /// in place of the global HOOKS, for the same reason as LOCAL_MOUNTS.
+    static LOCAL_HOOKS: RefCell<Hooks> = const { RefCell::new(Hooks::new()) };
+}
+
+// Providers mounted via `mount` along with the working directory when it lies outside of the
+// current provider
+#[derive(Clone, Default)]
+struct Mounts {
+    // Mount points paired with the providers mounted there ordered with the deepest first
+    points: Vec<(PathBuf, Arc<Vfs>)>,
+
+    // Working directory set via `set_cwd` when it is in a mounted provider or a directory leading
+    // to a mount point as the current provider can't change to a directory it doesn't contain
+    cwd: Option<PathBuf>,
+}
+
+impl Mounts {
+    const fn new() -> Self {
+        Self { points: Vec::new(), cwd: None }
+    }
+
+    // Resolve the given path to absolute form relative to the working directory
+    fn abs<T: AsRef<Path>>(&self, vfs: &Vfs, path: T) -> RvResult<PathBuf> {
+        match &self.cwd {
+            Some(cwd) => {
+                let path = path.as_ref().expand()?;
+                vfs.abs(if path.is_relative() { cwd.join(path) } else { path })
+            },
+            None => vfs.abs(path),
+        }
+    }
+
+    // Returns the mounted provider owning the given absolute path if any
+    fn owner(&self, abs: &Path) -> Option<&Arc<Vfs>> {
+        self.points.iter().find(|(x, _)| abs.starts_with(x)).map(|(_, vfs)| vfs)
+    }
+
+    // Returns true if the given absolute path is a directory leading to a mount point. These need
+    // not exist in the provider owning them so are treated as directories regardless.
+    fn leads_to(&self, abs: &Path) -> bool {
+        self.points.iter().any(|(x, _)| x != abs && x.starts_with(abs))
+    }
+}
+
+// Facade operations that modify the filesystem and are rejected by `set_readonly`
+const MUTATING: &[&str] = &[
+    "append", "append_all", "append_line", "append_lines", "chmod", "chmod_b", "chown", "chown_b", "copy",
+    "copy_b", "mkdir_m", "mkdir_p", "mkfile", "mkfile_m", "mount", "move_p", "remove", "remove_all", "set_cwd",
+    "symlink", "umount", "write", "write_all", "write_lines",
+];
+
+// Settings applied to facade calls before they are routed to a provider
+struct Hooks {
+    readonly: bool,
+    calls: Option<Vec<Call>>,
+    faults: Vec<Fault>,
+    latencies: Vec<Latency>,
+    clock: Duration,
+}
+
+impl Hooks {
+    const fn new() -> Self {
+        Self { readonly: false, calls: None, faults: Vec::new(), latencies: Vec::new(), clock: Duration::ZERO }
+    }
+
+    // Returns true if any of the settings would affect a facade call
+    fn active(&self) -> bool {
+        self.readonly || self.calls.is_some() || !self.faults.is_empty() || !self.latencies.is_empty()
+    }
+
+    // Check the given facade call returning the error it should fail with if any
+    fn check(&mut self, op: &str, paths: &[PathBuf]) -> Option<RvError> {
+        if self.readonly && MUTATING.contains(&op) {
+            let paths = paths.iter().map(|x| x.display().to_string()).collect::<Vec<_>>().join(", ");
+            let msg = format!("Read-only vfs rejected {}: {}", op, paths);
+            return Some(io::Error::new(io::ErrorKind::ReadOnlyFilesystem, msg).into());
+        }
+        for latency in self.latencies.iter().filter(|x| x.matches(op, paths)) {
+            self.clock += latency.delay;
+        }
+
+        // Every matching fault counts the call even when an earlier one already fired
+        let mut err = None;
+        for fault in self.faults.iter_mut().filter(|x| x.matches(op, paths)) {
+            if fault.fire() && err.is_none() {
+                let paths = paths.iter().map(|x| x.display().to_string()).collect::<Vec<_>>().join(", ");
+                let msg = format!("Injected fault for {}: {} ({})", op, paths, fault.kind);
+                err = Some(io::Error::new(fault.kind, msg).into());
+            }
+        }
+        err
+    }
+
+    // Advance the clock by the time the given bytes take at any matching bandwidth limits
+    fn transfer(&mut self, op: &str, paths: &[PathBuf], bytes: usize) {
+        for latency in self.latencies.iter().filter(|x| x.matches(op, paths)) {
+            if let Some(bandwidth) = latency.bandwidth.filter(|x| *x > 0) {
+                let nanos = bytes as u128 * 1_000_000_000 / bandwidth as u128;
+                self.clock += Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX));
+            }
+        }
+    }
+
+    // Log the given facade call and its result when recording
+    fn record<R: Outcome>(&mut self, op: &'static str, args: Vec<PathBuf>, result: &R) {
+        if let Some(calls) = self.calls.as_mut() {
+            calls.push(Call { op, args, result: result.summary() });
+        }
+    }
+}
+
+/// A failure injected into matching facade calls via `inject`
+///
+/// * Matches calls to the given facade function e.g. `write_all`
+/// * Optionally only matches calls given a path matching an absolute path glob where `*` matches
+///   within a path component, `**` matches across components and `?` matches a single character
+/// * Fires on every matching call unless limited to the Nth via `nth` or a seeded probability via
+///   `probability`, in which case the same seed always fires on the same calls
+/// * Fails the call with an io error of the given kind, `io::ErrorKind::Other` by default
+///
+/// ### Examples
+/// ```
+/// use std::io;
+///
+/// use rivia_vfs::prelude::*;
+///
+/// let fault = vfs::Fault::new("write_all").glob("/etc/**").kind(io::ErrorKind::StorageFull).nth(2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fault {
+    op: String,
+    glob: Option<String>,
+    kind: io::ErrorKind,
+    nth: Option<usize>,
+    probability: Option<f64>,
+    seed: u64,
+    count: usize,
+}
+
+impl Fault {
+    /// Create a new fault for calls to the given facade function
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let fault = vfs::Fault::new("read_all");
+    /// ```
+    pub fn new<T: AsRef<str>>(op: T) -> Self {
+        Self {
+            op: op.as_ref().to_string(),
+            glob: None,
+            kind: io::ErrorKind::Other,
+            nth: None,
+            probability: None,
+            seed: 0,
+            count: 0,
+        }
+    }
+
+    /// Only match calls given a path matching the given absolute path glob
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let fault = vfs::Fault::new("read_all").glob("/etc/*.toml");
+    /// ```
+    pub fn glob<T: AsRef<str>>(mut self, glob: T) -> Self {
+        self.glob = Some(glob.as_ref().to_string());
+        self
+    }
+
+    /// Fail matching calls with an io error of the given kind
+    ///
+    /// ### Examples
+    /// ```
+    /// use std::io;
+    ///
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let fault = vfs::Fault::new("read_all").kind(io::ErrorKind::PermissionDenied);
+    /// ```
+    pub fn kind(mut self, kind: io::ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Only fire on the Nth matching call counting from 1
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let fault = vfs::Fault::new("write_all").nth(3);
+    /// ```
+    pub fn nth(mut self, nth: usize) -> Self {
+        self.nth = Some(nth);
+        self
+    }
+
+    /// Only fire on matching calls with the given probability between 0.0 and 1.0
+    ///
+    /// * The given seed makes the calls that fire repeatable between runs
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let fault = vfs::Fault::new("write_all").probability(0.1, 42);
+    /// ```
+    pub fn probability(mut self, probability: f64, seed: u64) -> Self {
+        self.probability = Some(probability);
+        self.seed = seed;
+        self
+    }
+
+    // Returns true if the given facade call is one this fault applies to
+    fn matches(&self, op: &str, paths: &[PathBuf]) -> bool {
+        matches_call(&self.op, self.glob.as_deref(), op, paths)
+    }
+
+    // Count a matching call returning true if the fault fires on it
+    fn fire(&mut self) -> bool {
+        self.count += 1;
+        if self.nth.is_some_and(|x| x != self.count) {
+            return false;
+        }
+        match self.probability {
+            // SplitMix64 is plenty for repeatable test failures without pulling in a rand crate
+            Some(probability) => {
+                self.seed = self.seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut x = self.seed;
+                x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                x ^= x >> 31;
+                ((x >> 11) as f64 / (1u64 << 53) as f64) < probability
+            },
+            None => true,
+        }
+    }
+}
+
+/// Simulated latency added to matching facade calls via `add_latency`
+///
+/// * Matches calls to the given facade function e.g. `read_all`
+/// * Optionally only matches calls given a path matching an absolute path glob the same as a
+///   [`Fault`]
+/// * Advances the virtual clock read via `clock` by the given delay on every matching call
+/// * Advances the virtual clock by the time the data takes at the given bandwidth in bytes per
+///   second for calls that transfer data i.e. `read_all`, `write_all`, `append_all` and the readers
+///   and writers returned by `read`, `write` and `append`
+///
+/// ### Examples
+/// ```
+/// use std::time::Duration;
+///
+/// use rivia_vfs::prelude::*;
+///
+/// let latency = vfs::Latency::new("read_all").glob("/etc/**").delay(Duration::from_millis(5));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Latency {
+    op: String,
+    glob: Option<String>,
+    delay: Duration,
+    bandwidth: Option<u64>,
+}
+
+impl Latency {
+    /// Create a new latency for calls to the given facade function
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let latency = vfs::Latency::new("read_all");
+    /// ```
+    pub fn new<T: AsRef<str>>(op: T) -> Self {
+        Self { op: op.as_ref().to_string(), glob: None, delay: Duration::ZERO, bandwidth: None }
+    }
+
+    /// Only match calls given a path matching the given absolute path glob
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let latency = vfs::Latency::new("read_all").glob("/etc/*.toml");
+    /// ```
+    pub fn glob<T: AsRef<str>>(mut self, glob: T) -> Self {
+        self.glob = Some(glob.as_ref().to_string());
+        self
+    }
+
+    /// Delay every matching call by the given duration
+    ///
+    /// ### Examples
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let latency = vfs::Latency::new("mkdir_p").delay(Duration::from_millis(5));
+    /// ```
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Limit the data transferred by matching calls to the given bytes per second
+    ///
+    /// * A bandwidth of 0 is treated as unlimited
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// let latency = vfs::Latency::new("write_all").bandwidth(1024 * 1024);
+    /// ```
+    pub fn bandwidth(mut self, bandwidth: u64) -> Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    // Returns true if the given facade call is one this latency applies to
+    fn matches(&self, op: &str, paths: &[PathBuf]) -> bool {
+        matches_call(&self.op, self.glob.as_deref(), op, paths)
+    }
+}
+
+// Returns true if the given facade call is to the target function and, when a glob is given, has a
+// path matching it
+fn matches_call(target: &str, glob: Option<&str>, op: &str, paths: &[PathBuf]) -> bool {
+    target == op
+        && match glob {
+            Some(glob) => paths.iter().any(|x| glob_match(glob.as_bytes(), x.as_os_str().as_bytes())),
+            None => true,
+        }
+}
+
+// Match the given path against the given glob where `*` matches any characters within a path
+// component, `**` any characters across components and `?` any single character but `/`
+fn glob_match(glob: &[u8], path: &[u8]) -> bool {
+    match glob {
+        [] => path.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| glob_match(rest, &path[i..])),
+        [b'*', rest @ ..] => {
+            (0..=path.len()).take_while(|&i| i == 0 || path[i - 1] != b'/').any(|i| glob_match(rest, &path[i..]))
+        },
+        [b'?', rest @ ..] => matches!(path, [x, tail @ ..] if *x != b'/' && glob_match(rest, tail)),
+        [c, rest @ ..] => matches!(path, [x, tail @ ..] if x == c && glob_match(rest, tail)),
+    }
+}
+
+/// A facade call logged while recording via `set_recording`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Call {
+    /// Name of the facade function that was called e.g. `write_all`
+    pub op: &'static str,
+
+    /// Path arguments the call was given made absolute
+    pub args: Vec<PathBuf>,
+
+    /// Result of the call with any error in its display form
+    pub result: Result<(), String>,
+}
+
+/// Get the current vfs provider
+///
+/// * Returns the thread local override when one has been set via `set_local`
+/// * Falls back to the global `VFS` singleton otherwise
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::provider().exists(vfs::root()));
+/// ```
+pub fn provider() -> Arc<Vfs> {
+    match LOCAL.with(|x| x.borrow().clone()) {
+        Some(vfs) => vfs,
+        None => VFS.load_full(),
+    }
+}
+
+// Call the given function with the current vfs provider
+//
+// This is the hot path for all facade functions and avoids cloning the Arc as is done in
+// `provider` by borrowing the thread local override or lock free loading the global VFS.
+fn with<R, F: FnOnce(&Vfs) -> R>(f: F) -> R {
+    LOCAL.with(|x| match &*x.borrow() {
+        Some(vfs) => f(vfs),
+        None => f(&VFS.load()),
+    })
+}
+
+// Call the given function with the mounts that apply to the current vfs provider along with it
+fn with_mounts<R, F: FnOnce(&Mounts, &Vfs) -> R>(f: F) -> R {
+    LOCAL.with(|x| match &*x.borrow() {
+        Some(vfs) => LOCAL_MOUNTS.with(|mounts| f(&mounts.borrow(), vfs)),
+        None => f(&MOUNTS.load(), &VFS.load()),
+    })
+}
+
+/// Create a new vfs provider from the given URI
+///
+/// * `memfs://` creates a new Memfs
+/// * `file://` or `file:///` creates a new Stdfs
+///
+/// ### Errors
+/// * CoreError::Msg when the URI is malformed or its scheme isn't supported
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set(vfs::from_uri("memfs://").unwrap()).is_ok());
+/// assert!(vfs::is_memfs());
+/// assert!(vfs::from_uri("foo://").is_err());
+/// ```
+pub fn from_uri<T: AsRef<str>>(uri: T) -> RvResult<Vfs> {
+    let uri = uri.as_ref().trim();
+    let (scheme, path) = match uri.split_once("://") {
+        Some(x) => x,
+        None => return Err(CoreError::msg(format!("Invalid vfs uri given: {}", uri)).into()),
+    };
+    let vfs = match scheme.to_lowercase().as_str() {
+        "memfs" => Vfs::memfs(),
+        "file" => Vfs::stdfs(),
+        _ => return Err(CoreError::msg(format!("Unsupported vfs uri scheme given: {}", uri)).into()),
+    };
+
+    // Neither provider supports being rooted anywhere but the root
+    if !path.is_empty() && path != "/" {
+        return Err(CoreError::msg(format!("Unsupported vfs uri path given: {}", uri)).into());
+    }
+    Ok(vfs)
+}
+
+/// Set the current vfs backend from the `RIVIA_VFS` environment variable
+///
+/// The `VFS` singleton is initialized from the environment variable automatically but silently
+/// falls back to Stdfs when the value is invalid. Call this early on to surface such errors.
+///
+/// * Leaves the current provider alone when the environment variable isn't set
+///
+/// ### Errors
+/// * CoreError::Msg when the environment variable isn't a supported provider URI
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// std::env::set_var(vfs::VFS_ENV, "foo://");
+/// assert!(vfs::is_stdfs());
+/// assert!(vfs::init_from_env().is_err());
+///
+/// std::env::set_var(vfs::VFS_ENV, "memfs://");
+/// assert!(vfs::init_from_env().is_ok());
+/// assert!(vfs::is_memfs());
+/// ```
+pub fn init_from_env() -> RvResult<()> {
+    match std::env::var(VFS_ENV) {
+        Ok(uri) => set(from_uri(uri)?),
+        Err(std::env::VarError::NotPresent) => Ok(()),
+        Err(e) => Err(CoreError::msg(format!("Invalid vfs uri given: {}", e)).into()),
+    }
+}
+
+/// Set a vfs backend override for the current thread only
+///
+/// All facade functions and `assert_*!` macros called from the current thread will use the given
+/// provider rather than the global `VFS` until `unset_local` is called. Other threads are
+/// unaffected which makes it possible to give each test thread its own isolated Memfs.
+///
+/// * Use `set` to replace the vfs provider for all threads
+/// * Drops any mounts made from the current thread under a previous override
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert_mkfile!("foo");
+/// std::thread::spawn(|| {
+///     assert!(vfs::set_local(Vfs::memfs()).is_ok());
+///     assert_no_file!("foo");
+/// })
+/// .join()
+/// .unwrap();
+/// assert_is_file!("foo");
+/// ```
+pub fn set_local(vfs: Vfs) -> RvResult<()> {
+    LOCAL.with(|x| *x.borrow_mut() = Some(Arc::new(vfs)));
+    LOCAL_MOUNTS.with(|x| *x.borrow_mut() = Mounts::new());
+    LOCAL_HOOKS.with(|x| *x.borrow_mut() = Hooks::new());
+    Ok(())
+}
+
+/// Remove the vfs backend override for the current thread returning it if one was set
+///
+/// * Facade functions called from the current thread will once again use the global `VFS`
+/// * Drops any mounts and facade settings e.g. `set_readonly` made from the current thread while
+///   the override was set
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::unset_local().is_some());
+/// assert!(vfs::unset_local().is_none());
+/// ```
+pub fn unset_local() -> Option<Arc<Vfs>> {
+    LOCAL_MOUNTS.with(|x| *x.borrow_mut() = Mounts::new());
+    LOCAL_HOOKS.with(|x| *x.borrow_mut() = Hooks::new());
+    LOCAL.with(|x| x.borrow_mut().take())
+}
+
+/// Identifies which backend a vfs provider is using
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ProviderKind {
+    Stdfs,
+    Memfs,
+}
+
+impl ProviderKind {
+    /// Return the human readable name of the backend
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// assert_eq!(vfs::ProviderKind::Memfs.name(), "Memfs");
+    /// ```
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProviderKind::Stdfs => "Stdfs",
+            ProviderKind::Memfs => "Memfs",
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl From<&Vfs> for ProviderKind {
+    fn from(vfs: &Vfs) -> Self {
+        match vfs {
+            Vfs::Stdfs(_) => ProviderKind::Stdfs,
+            Vfs::Memfs(_) => ProviderKind::Memfs,
+        }
+    }
+}
+
+/// Return the kind of backend the current vfs provider is using
+///
+/// * Honors the thread local override when one has been set via `set_local`
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert_eq!(vfs::provider_kind(), vfs::ProviderKind::Memfs);
+/// assert_eq!(vfs::provider_kind().to_string(), "Memfs");
+/// ```
+pub fn provider_kind() -> ProviderKind {
+    with(|vfs| ProviderKind::from(vfs))
+}
+
+/// Returns true if the current vfs provider is Memfs
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::is_memfs());
+/// ```
+pub fn is_memfs() -> bool {
+    provider_kind() == ProviderKind::Memfs
+}
+
+/// Returns true if the current vfs provider is Stdfs
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::is_stdfs());
+/// ```
+pub fn is_stdfs() -> bool {
+    provider_kind() == ProviderKind::Stdfs
+}
+
+/// Set the current vfs backend being used
+///
+/// Following the promoting pattern we can switch the Vfs backend for the given implementation
+/// while allowing current consumers that have a reference to the previous Vfs backend
+/// implementation to complete their operations safely.
+///
+/// * Replaces the thread local override instead when one has been set via `set_local`
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set(Vfs::memfs()).is_ok());
+/// ```
+pub fn set(vfs: Vfs) -> RvResult<()> {
+    // Replace the existing arc with a new one allowing the original to continue to
+    // operate as long as there are references to it.
+    let vfs = Arc::new(vfs);
+    if !update_local(|_| vfs.clone()) {
+        VFS.store(vfs);
+    }
+    Ok(())
+}
+
+/// Switch the current vfs provider to Memfs if not already
+///
+/// * Switches the thread local override instead when one has been set via `set_local`
+/// * Use `set` to simply replace the vfs provider without checks
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// ```
+pub fn set_memfs() -> RvResult<()> {
+    let memfs = |vfs: &Arc<Vfs>| match **vfs {
+        Vfs::Memfs(ref _vfs) => vfs.clone(),
+        _ => Arc::new(Vfs::memfs()),
+    };
+
+    // Only set if needed using read-copy-update to avoid collisions
+    if !update_local(memfs) {
+        VFS.rcu(|vfs| memfs(vfs));
+    }
+    Ok(())
+}
+
+/// Switch the current vfs provider to Stdfs if not already
+///
+/// * Switches the thread local override instead when one has been set via `set_local`
+/// * Use `set` to simply replace the vfs provider without checks
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::set_stdfs().is_ok());
+/// assert!(vfs::is_stdfs());
+/// ```
+pub fn set_stdfs() -> RvResult<()> {
+    let stdfs = |vfs: &Arc<Vfs>| match **vfs {
+        Vfs::Stdfs(ref _vfs) => vfs.clone(),
+        _ => Arc::new(Vfs::stdfs()),
+    };
+
+    // Only set if needed using read-copy-update to avoid collisions
+    if !update_local(stdfs) {
+        VFS.rcu(|vfs| stdfs(vfs));
+    }
+    Ok(())
+}
+
+// Update the thread local override with the given function returning false when none is set
+fn update_local<F: FnOnce(&Arc<Vfs>) -> Arc<Vfs>>(f: F) -> bool {
+    LOCAL.with(|x| match x.borrow_mut().as_mut() {
+        Some(vfs) => {
+            *vfs = f(vfs);
+            true
+        },
+        None => false,
+    })
+}
+
+/// Replace the current vfs backend returning the previous one
+///
+/// Works just like `set` but hands back the replaced provider so that it can be inspected or
+/// reinstalled later.
+///
+/// * Replaces the thread local override instead when one has been set via `set_local`
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// let prev = vfs::replace(Vfs::stdfs());
+/// assert!(vfs::is_stdfs());
+/// assert_eq!(vfs::ProviderKind::from(&*prev), vfs::ProviderKind::Memfs);
+/// ```
+pub fn replace(vfs: Vfs) -> Arc<Vfs> {
+    let vfs = Arc::new(vfs);
+    let mut prev = None;
+    update_local(|x| {
+        prev = Some(x.clone());
+        vfs.clone()
+    });
+    prev.unwrap_or_else(|| VFS.swap(vfs))
+}
+
+/// Guard returned from [`scoped`] that restores the previous vfs provider when dropped
+///
+/// * Restoration happens on normal scope exit as well as during a panic unwind
+/// * Restores the thread local override rather than the global provider when it was created while
+///   one was set via `set_local`, in which case it must be dropped on the same thread
+/// * Nested guards restore in LIFO order as they are dropped but only within a single thread. Each
+///   guard reinstalls exactly the provider it replaced, so overlapping guards on different threads
+///   swapping the global provider can leave the wrong provider installed for good
+#[must_use = "the previous vfs provider is restored as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct VfsGuard {
+    prev: Option<Arc<Vfs>>,
+    local: bool,
+}
+
+impl Drop for VfsGuard {
+    fn drop(&mut self) {
+        if let Some(prev) = self.prev.take() {
+            if !self.local {
+                VFS.store(prev);
+            } else {
+                update_local(|_| prev);
+            }
+        }
+    }
+}
+
+/// Temporarily set the current vfs backend until the returned guard is dropped
+///
+/// Swaps in the given provider and hands back a [`VfsGuard`] holding the previous one. When the
+/// guard goes out of scope, including during a panic unwind, the previous provider is reinstalled.
+///
+/// * Use `set` to permanently replace the vfs provider
+/// * Swaps the thread local override instead when one has been set via `set_local`
+/// * See [`VfsGuard`] for the restore order when guards overlap
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// let prev = vfs::VFS.load_full();
+/// {
+///     let _outer = vfs::scoped(Vfs::memfs());
+///     let outer = vfs::VFS.load_full();
+///     {
+///         let _inner = vfs::scoped(Vfs::memfs());
+///         assert!(!Arc::ptr_eq(&vfs::VFS.load(), &outer));
+///     }
+///     assert!(Arc::ptr_eq(&vfs::VFS.load(), &outer));
+/// }
+/// assert!(Arc::ptr_eq(&vfs::VFS.load(), &prev));
+///
+/// // The previous provider is restored even when panicking
+/// let result = std::panic::catch_unwind(|| {
+///     let _guard = vfs::scoped(Vfs::memfs());
+///     panic!("unwinding");
+/// });
+/// assert!(result.is_err());
+/// assert!(Arc::ptr_eq(&vfs::VFS.load(), &prev));
+/// ```
+pub fn scoped(vfs: Vfs) -> VfsGuard {
+    let local = LOCAL.with(|x| x.borrow().is_some());
+    VfsGuard { prev: Some(replace(vfs)), local }
+}
+
+/// Handle to a single vfs provider captured at a point in time
+///
+/// Dereferences to the captured [`Vfs`] so the full facade API is available as methods e.g.
+/// `handle.write_all(path, data)`. All calls made through a handle use the same provider
+/// regardless of any later calls to `set`, `scoped` or `set_local`.
+#[derive(Clone, Debug)]
+pub struct VfsHandle {
+    vfs: Arc<Vfs>,
+}
+
+impl VfsHandle {
+    /// Return the kind of backend this handle's provider is using
+    ///
+    /// ### Examples
+    /// ```
+    /// use rivia_vfs::prelude::*;
+    ///
+    /// assert!(vfs::set_memfs().is_ok());
+    /// assert_eq!(vfs::handle().kind(), vfs::ProviderKind::Memfs);
+    /// ```
+    pub fn kind(&self) -> ProviderKind {
+        ProviderKind::from(&*self.vfs)
+    }
+}
+
+impl Deref for VfsHandle {
+    type Target = Vfs;
+
+    fn deref(&self) -> &Vfs {
+        &self.vfs
+    }
+}
+
+impl From<Arc<Vfs>> for VfsHandle {
+    fn from(vfs: Arc<Vfs>) -> Self {
+        Self { vfs }
+    }
+}
+
+/// Capture the current vfs provider in a handle
+///
+/// Allows for running a batch of operations against one consistent provider even if the global
+/// provider is switched partway through.
+///
+/// * Honors the thread local override when one has been set via `set_local`
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// let handle = vfs::handle();
+/// assert!(handle.write_all("file1", "this is a test").is_ok());
+///
+/// // Switching providers doesn't affect the handle
+/// assert!(vfs::set(Vfs::memfs()).is_ok());
+/// assert!(handle.copy("file1", "file2").is_ok());
+/// assert!(handle.chmod("file2", 0o600).is_ok());
+/// assert_eq!(handle.read_all("file2").unwrap(), "this is a test");
+/// assert_no_file!("file2");
+/// ```
+pub fn handle() -> VfsHandle {
+    VfsHandle::from(provider())
+}
+
+/// Register the given vfs provider under the given name
+///
+/// Named providers live alongside the global `VFS` singleton allowing multiple independent
+/// filesystems to be used at once e.g. a Stdfs for input and a Memfs for staging.
+///
+/// * Registering an existing name replaces the previous provider
+/// * Use `named` to get a handle to the registered provider
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::register("scratch", Vfs::memfs()).is_ok());
+/// assert!(vfs::named("scratch").unwrap().mkfile("file").is_ok());
+/// ```
+pub fn register<T: AsRef<str>>(name: T, vfs: Vfs) -> RvResult<()> {
+    let vfs = Arc::new(vfs);
+    REGISTRY.rcu(|registry| {
+        let mut registry = HashMap::clone(registry);
+        registry.insert(name.as_ref().to_owned(), vfs.clone());
+        registry
+    });
+    Ok(())
+}
+
+/// Remove the vfs provider registered under the given name returning it if one was found
+///
+/// * Existing handles to the provider will continue to work
+/// * Returns `None` without unmounting when rejected by `set_readonly`
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::register("scratch", Vfs::memfs()).is_ok());
+/// assert!(vfs::unregister("scratch").is_some());
+/// assert!(vfs::named("scratch").is_err());
+/// ```
+pub fn unregister<T: AsRef<str>>(name: T) -> Option<VfsHandle> {
+    let prev = REGISTRY.rcu(|registry| {
+        let mut registry = HashMap::clone(registry);
+        registry.remove(name.as_ref());
+        registry
+    });
+    prev.get(name.as_ref()).cloned().map(VfsHandle::from)
+}
+
+/// Get a handle to the vfs provider registered under the given name
+///
+/// ### Errors
+/// * VfsError::Unavailable when no provider has been registered with the given name
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::register("scratch", Vfs::memfs()).is_ok());
+/// let scratch = vfs::named("scratch").unwrap();
+/// assert!(scratch.write_all("file", "this is a test").is_ok());
+/// assert_eq!(scratch.read_all("file").unwrap(), "this is a test");
+/// ```
+pub fn named<T: AsRef<str>>(name: T) -> RvResult<VfsHandle> {
+    match REGISTRY.load().get(name.as_ref()) {
+        Some(vfs) => Ok(VfsHandle::from(vfs.clone())),
+        None => Err(VfsError::Unavailable.into()),
+    }
+}
+
+/// Mount the given vfs provider at the given path
+///
+/// Facade calls for paths at or below the mount point are routed to the mounted provider while
+/// everything else continues to go to the current provider. Paths are resolved to absolute form
+/// using the current provider and passed to the mounted provider unchanged.
+///
+/// * The mount point directory is created in the mounted provider only, nothing is created in the
+///   current provider or any provider the mount is nested in
+/// * Mounting at an existing mount point replaces the previous provider
+/// * Nested mounts are supported with the deepest mount point owning a path
+/// * Listings e.g. `dirs` and `all_paths` include mount points, the directories leading to them
+///   and mounted content. `exists` and `is_dir` report the leading directories as directories
+/// * `copy` and `move_p` work across mounts while `copy_b` requires both paths on one provider
+/// * `entries` doesn't include mount points or mounted content as rivia's `Entries` can't be
+///   extended outside of rivia
+/// * `set_cwd` accepts directories in mounted providers and leading to mount points tracking the
+///   working directory with the mounts. It reverts to the current provider's working directory
+///   once the provider it is in is unmounted
+/// * `remove`, `remove_all` and `move_p` fail for paths that are or contain a mount point
+/// * The `assert_*!` macros are routed as well using `provider_at`
+/// * Mounts are global unless made while a thread local override is set via `set_local` in which
+///   case they only apply to the current thread and are dropped along with the override
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::mount("/tmp", Vfs::memfs()).is_ok());
+/// assert_mkdir_p!("/etc");
+/// assert_write_all!("/etc/foo", "real");
+/// assert_write_all!("/tmp/foo", "scratch");
+/// assert_iter_eq(vfs::dirs("/").unwrap(), vec![PathBuf::from("/etc"), PathBuf::from("/tmp")]);
+/// assert!(vfs::umount("/tmp").is_some());
+/// assert_no_file!("/tmp/foo");
+/// ```
+pub fn mount<T: AsRef<Path>>(path: T, vfs: Vfs) -> RvResult<()> {
+    let path = path.as_ref();
+    call("mount", &[path], || {
+        let path = with_mounts(|mounts, vfs| mounts.abs(vfs, path))?;
+        vfs.mkdir_p(&path)?;
+        let vfs = Arc::new(vfs);
+        update_mounts(|mounts| {
+            mounts.points.retain(|(x, _)| x != &path);
+            mounts.points.push((path.clone(), vfs.clone()));
+
+            // Order deepest mount points first so they take precedence during routing
+            mounts.points.sort_by_key(|(x, _)| std::cmp::Reverse(x.components().count()));
+        });
+        Ok(())
+    })
+}
+
+/// Remove the vfs provider mounted at the given path returning it if one was found
+///
+/// * Existing handles to the provider will continue to work
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::mount("/tmp", Vfs::memfs()).is_ok());
+/// assert!(vfs::umount("/tmp").is_some());
+/// assert!(vfs::umount("/tmp").is_none());
+/// ```
+pub fn umount<T: AsRef<Path>>(path: T) -> Option<VfsHandle> {
+    let path = path.as_ref();
+    call("umount", &[path], || {
+        let path = with_mounts(|mounts, vfs| mounts.abs(vfs, path)).ok()?;
+        let prev = update_mounts(|mounts| {
+            mounts.points.retain(|(x, _)| x != &path);
 
-use lazy_static::lazy_static;
-use rivia::prelude::*;
+            // Fall back to the current provider's working directory if it was in the unmounted one
+            if mounts.cwd.as_ref().is_some_and(|x| mounts.owner(x).is_none() && !mounts.leads_to(x)) {
+                mounts.cwd = None;
+            }
+        });
+        prev.points.into_iter().find(|(x, _)| x == &path).map(|(_, vfs)| VfsHandle::from(vfs))
+    })
+}
 
-/// All essential symbols in a simple consumable form
+// Update the mounts that apply to the current vfs provider returning the previous mounts
+fn update_mounts<F: Fn(&mut Mounts)>(f: F) -> Mounts {
+    if LOCAL.with(|x| x.borrow().is_some()) {
+        return LOCAL_MOUNTS.with(|x| {
+            let prev = x.borrow().clone();
+            f(&mut x.borrow_mut());
+            prev
+        });
+    }
+    let prev = MOUNTS.rcu(|mounts| {
+        let mut mounts = Mounts::clone(mounts);
+        f(&mut mounts);
+        mounts
+    });
+    Mounts::clone(&prev)
+}
+
+/// Get the vfs provider owning the given path
+///
+/// * Returns the mounted provider with the deepest mount point containing the given path
+/// * Falls back to the current provider as returned by `provider` otherwise
 ///
 /// ### Examples
 /// ```
 /// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::mount("/tmp", Vfs::memfs()).is_ok());
+/// assert_write_all!("/tmp/foo", "scratch");
+/// assert_vfs_is_file!(vfs::provider_at("/tmp"), "/tmp/foo");
+/// assert_vfs_no_file!(vfs::provider(), "/tmp/foo");
 /// ```
-pub mod prelude {
-    pub use rivia::prelude::*;
+pub fn provider_at<T: AsRef<Path>>(path: T) -> Arc<Vfs> {
+    let mounted = with_mounts(|mounts, vfs| mounts.owner(&mounts.abs(vfs, path).ok()?).cloned());
+    mounted.unwrap_or_else(provider)
+}
 
-    // Export macros by name
-    pub use crate::{
-        assert_copyfile, assert_exists, assert_is_dir, assert_is_file, assert_is_symlink, assert_memfs_setup,
-        assert_mkdir_m, assert_mkdir_p, assert_mkfile, assert_no_dir, assert_no_exists, assert_no_file,
-        assert_no_symlink, assert_read_all, assert_readlink, assert_readlink_abs, assert_remove,
-        assert_remove_all, assert_setup, assert_stdfs_setup, assert_symlink, assert_write_all,
-    };
+/// Reject all mutating facade calls with a read-only filesystem error
+///
+/// * Mutating calls e.g. `write_all`, `mkdir_p`, `remove_all`, `set_cwd` and `mount` fail before
+///   reaching any provider including mounted ones
+/// * Reading calls e.g. `read_all` and `exists` are unaffected
+/// * Applies to all threads unless made under a thread local override set via `set_local`
+/// * The `assert_*!` macros operate on the providers directly and aren't affected
+/// * Providers obtained via `provider`, `provider_at`, `handle` or `named` are used directly and
+///   aren't affected either e.g. `vfs::handle().write_all(..)` still writes
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert_write_all!("/foo", "bar");
+/// assert!(vfs::set_readonly(true).is_ok());
+/// assert!(vfs::readonly());
+/// assert_eq!(vfs::read_all("/foo").unwrap(), "bar");
+/// assert!(vfs::write_all("/foo", "baz").is_err());
+/// assert!(vfs::remove("/foo").is_err());
+/// assert!(vfs::set_readonly(false).is_ok());
+/// assert!(vfs::write_all("/foo", "baz").is_ok());
+/// ```
+pub fn set_readonly(readonly: bool) -> RvResult<()> {
+    update_hooks(|hooks| hooks.readonly = readonly);
+    Ok(())
+}
 
-    // Nest global vfs functions for ergonomics
-    pub mod vfs {
-        pub use crate::*;
-    }
+/// Returns true if mutating facade calls are being rejected via `set_readonly`
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(!vfs::readonly());
+/// assert!(vfs::set_readonly(true).is_ok());
+/// assert!(vfs::readonly());
+/// ```
+pub fn readonly() -> bool {
+    update_hooks(|hooks| hooks.readonly)
 }
 
-lazy_static! {
-    /// VFS is a virtual filesystem singleton providing an implementation of Vfs that defaults to
-    /// Stdfs but can be changed dynamically to any variant of the Vfs enum.
-    ///
-    /// Arc is used here to provide the guarantee that the shared VFS instance is thread safe and
-    /// is wrapped in a RwLock to provide the ability to change the VFS backend implementation if
-    /// desired following the promoting pattern rather than interior mutability i.e. Arc<RwLock>>.
-    /// Since changing the backend will be a rare occurance RwLock is used here rather than Mutex
-    /// to provide many readers but only one writer which should be as efficient as possible.
-    /// https://blog.sentry.io/2018/04/05/you-cant-rust-that
-    pub static ref VFS: RwLock<Arc<Vfs>> = RwLock::new(Arc::new(Vfs::stdfs()));
+/// Log every facade call made along with its path arguments and result
+///
+/// * Starting a recording clears any calls logged previously, stopping drops the log
+/// * Applies to all threads unless made under a thread local override set via `set_local`
+/// * The `assert_*!` macros operate on the providers directly and aren't recorded
+/// * Calls made by the `Chmod`, `Chown` and `Copier` builders execute inside rivia and only the
+///   `chmod_b`, `chown_b` and `copy_b` calls creating them are recorded
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::set_recording(true).is_ok());
+/// assert!(vfs::write_all("/foo", "bar").is_ok());
+/// assert!(vfs::read_all("/bar").is_err());
+/// assert_called!(write_all, "/foo");
+/// assert_not_called!(remove_all);
+/// assert_eq!(vfs::calls().len(), 2);
+/// assert!(vfs::calls()[1].result.is_err());
+/// ```
+pub fn set_recording(recording: bool) -> RvResult<()> {
+    update_hooks(|hooks| hooks.calls = if recording { Some(vec![]) } else { None });
+    Ok(())
 }
 
-/// Set the current vfs backend being used
+/// Returns the facade calls logged since recording was started via `set_recording`
 ///
-/// Following the promoting pattern we can switch the Vfs backend for the given implementation
-/// while allowing current consumers that have a reference to the previous Vfs backend
-/// implementation to complete their operations safely.
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::set_recording(true).is_ok());
+/// assert!(vfs::mkdir_p("/foo").is_ok());
+/// let call = vfs::Call { op: "mkdir_p", args: vec![PathBuf::from("/foo")], result: Ok(()) };
+/// assert_eq!(vfs::calls(), vec![call]);
+/// ```
+pub fn calls() -> Vec<Call> {
+    update_hooks(|hooks| hooks.calls.clone().unwrap_or_default())
+}
+
+/// Fail matching facade calls as configured by the given [`Fault`]
+///
+/// * Only calls returning a `RvResult` can fail e.g. `exists` is never failed
+/// * Failures happen before the call reaches any provider so nothing is partially done
+/// * Applies to all threads unless made under a thread local override set via `set_local`
+/// * The `assert_*!` macros operate on the providers directly and aren't affected
 ///
 /// ### Examples
 /// ```
+/// use std::io;
+///
 /// use rivia_vfs::prelude::*;
 ///
-/// assert!(vfs::set(Vfs::memfs()).is_ok());
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// let fault = vfs::Fault::new("write_all").glob("/etc/*").kind(io::ErrorKind::StorageFull).nth(2);
+/// assert!(vfs::inject(fault).is_ok());
+/// assert_mkdir_p!("/etc");
+/// assert!(vfs::write_all("/etc/foo", "bar").is_ok());
+/// assert!(vfs::write_all("/etc/foo", "bar").is_err());
+/// assert!(vfs::write_all("/etc/foo", "bar").is_ok());
 /// ```
-pub fn set(vfs: Vfs) -> RvResult<()> {
-    // Replace the existing arc with a new one allowing the original to continue to
-    // operate as long as there are references to it.
-    *VFS.write().unwrap() = Arc::new(vfs);
+pub fn inject(fault: Fault) -> RvResult<()> {
+    update_hooks(|hooks| hooks.faults.push(fault));
     Ok(())
 }
 
-/// Switch the current vfs provider to Memfs if not already
+/// Remove all faults added via `inject`
 ///
-/// * Use `set` to simply replace the vfs provider without checks
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::inject(vfs::Fault::new("read_all")).is_ok());
+/// assert_write_all!("/foo", "bar");
+/// assert!(vfs::read_all("/foo").is_err());
+/// assert!(vfs::clear_faults().is_ok());
+/// assert!(vfs::read_all("/foo").is_ok());
+/// ```
+pub fn clear_faults() -> RvResult<()> {
+    update_hooks(|hooks| hooks.faults.clear());
+    Ok(())
+}
+
+/// Slow down matching facade calls as configured by the given [`Latency`]
+///
+/// * Time only passes on the virtual clock read via `clock` so nothing actually sleeps, which keeps
+///   tests of timeouts, progress reporting or cancellation fast and deterministic
+/// * Calls rejected via `set_readonly` never reach a provider and take no time
+/// * Applies to all threads unless made under a thread local override set via `set_local`
+/// * The `assert_*!` macros operate on the providers directly and aren't affected
 ///
 /// ### Examples
 /// ```
+/// use std::time::Duration;
+///
 /// use rivia_vfs::prelude::*;
 ///
-/// assert!(vfs::set_memfs().is_ok());
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// let latency = vfs::Latency::new("write_all").delay(Duration::from_millis(5)).bandwidth(1000);
+/// assert!(vfs::add_latency(latency).is_ok());
+/// assert!(vfs::write_all("/foo", "bar").is_ok());
+/// assert_eq!(vfs::clock(), Duration::from_millis(8));
 /// ```
-pub fn set_memfs() -> RvResult<()> {
-    // Take lock for whole context to avoid collisions
-    let mut guard = VFS.write().unwrap();
+pub fn add_latency(latency: Latency) -> RvResult<()> {
+    update_hooks(|hooks| hooks.latencies.push(latency));
+    Ok(())
+}
+
+/// Remove all latencies added via `add_latency` leaving the virtual clock as is
+///
+/// ### Examples
+/// ```
+/// use std::time::Duration;
+///
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::add_latency(vfs::Latency::new("mkdir_p").delay(Duration::from_secs(1))).is_ok());
+/// assert!(vfs::mkdir_p("/foo").is_ok());
+/// assert!(vfs::clear_latency().is_ok());
+/// assert!(vfs::mkdir_p("/bar").is_ok());
+/// assert_eq!(vfs::clock(), Duration::from_secs(1));
+/// ```
+pub fn clear_latency() -> RvResult<()> {
+    update_hooks(|hooks| hooks.latencies.clear());
+    Ok(())
+}
+
+/// Returns the time passed on the virtual clock advanced by latencies added via `add_latency`
+///
+/// * Code under test can measure elapsed time with this rather than the system clock
+///
+/// ### Examples
+/// ```
+/// use std::time::Duration;
+///
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert_eq!(vfs::clock(), Duration::ZERO);
+/// assert!(vfs::add_latency(vfs::Latency::new("exists").delay(Duration::from_millis(5))).is_ok());
+/// assert!(!vfs::exists("/foo"));
+/// assert_eq!(vfs::clock(), Duration::from_millis(5));
+/// ```
+pub fn clock() -> Duration {
+    update_hooks(|hooks| hooks.clock)
+}
+
+/// Set the virtual clock read via `clock` e.g. back to zero between measurements
+///
+/// ### Examples
+/// ```
+/// use std::time::Duration;
+///
+/// use rivia_vfs::prelude::*;
+///
+/// assert!(vfs::set_local(Vfs::memfs()).is_ok());
+/// assert!(vfs::set_clock(Duration::from_secs(60)).is_ok());
+/// assert_eq!(vfs::clock(), Duration::from_secs(60));
+/// ```
+pub fn set_clock(clock: Duration) -> RvResult<()> {
+    update_hooks(|hooks| hooks.clock = clock);
+    Ok(())
+}
+
+// Call the given function with the facade call settings that apply to the current vfs provider
+fn update_hooks<R, F: FnOnce(&mut Hooks) -> R>(f: F) -> R {
+    if LOCAL.with(|x| x.borrow().is_some()) {
+        return LOCAL_HOOKS.with(|x| f(&mut x.borrow_mut()));
+    }
+    let mut hooks = HOOKS.lock().unwrap_or_else(|e| e.into_inner());
+    let result = f(&mut hooks);
+    HOOKED.store(hooks.active(), Ordering::Release);
+    result
+}
+
+// Results of facade calls that may be failed or recorded by the facade call settings
+trait Outcome: Sized {
+    // Convert the given error into this result if the call is able to fail
+    fn from_err(err: RvError) -> Option<Self>;
+
+    // Summarize this result for the call log
+    fn summary(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<T> Outcome for RvResult<T> {
+    fn from_err(err: RvError) -> Option<Self> {
+        Some(Err(err))
+    }
+
+    fn summary(&self) -> Result<(), String> {
+        self.as_ref().map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+impl Outcome for bool {
+    fn from_err(_: RvError) -> Option<Self> {
+        None
+    }
+}
+
+impl Outcome for PathBuf {
+    fn from_err(_: RvError) -> Option<Self> {
+        None
+    }
+}
+
+impl Outcome for Option<PathBuf> {
+    fn from_err(_: RvError) -> Option<Self> {
+        None
+    }
+}
+
+// Failing to unmount leaves the provider mounted the same as when nothing is mounted
+impl Outcome for Option<VfsHandle> {
+    fn from_err(_: RvError) -> Option<Self> {
+        Some(None)
+    }
+}
+
+// Call the given facade operation applying the facade call settings e.g. `set_readonly`,
+// `set_recording` and `inject`
+//
+// This is the choke point for all facade functions that touch the filesystem so the settings are
+// only looked up here and skipped entirely when none are active.
+fn call<R: Outcome, F: FnOnce() -> R>(op: &'static str, paths: &[&Path], f: F) -> R {
+    if !hooked() {
+        return f();
+    }
+
+    // Resolve with the provider directly as `abs` is itself a facade call
+    let paths: Vec<PathBuf> = with_mounts(|mounts, vfs| {
+        paths.iter().map(|x| mounts.abs(vfs, x).unwrap_or_else(|_| x.to_path_buf())).collect()
+    });
+    let result = match update_hooks(|hooks| hooks.check(op, &paths)).and_then(R::from_err) {
+        Some(result) => result,
+        None => f(),
+    };
+    update_hooks(|hooks| hooks.record(op, paths, &result));
+    result
+}
+
+// Returns true if any facade call settings apply to the current vfs provider
+fn hooked() -> bool {
+    match LOCAL.with(|x| x.borrow().is_some()) {
+        true => LOCAL_HOOKS.with(|x| x.borrow().active()),
+        false => HOOKED.load(Ordering::Acquire),
+    }
+}
+
+// Advance the virtual clock by the time the given bytes take at any bandwidth limits matching the
+// given facade call
+fn transfer(op: &'static str, path: &Path, bytes: usize) {
+    if hooked() {
+        let path = with_mounts(|mounts, vfs| mounts.abs(vfs, path).unwrap_or_else(|_| path.to_path_buf()));
+        update_hooks(|hooks| hooks.transfer(op, &[path], bytes));
+    }
+}
+
+// Reader or writer returned by a facade call that advances the virtual clock as data flows through
+// it so bandwidth limits apply to streamed data the same as to whole files
+struct Metered<T> {
+    inner: T,
+    op: &'static str,
+    path: PathBuf,
+}
+
+impl<T> Metered<T> {
+    fn new(op: &'static str, path: &Path, inner: T) -> Self {
+        let path = with_mounts(|mounts, vfs| mounts.abs(vfs, path).unwrap_or_else(|_| path.to_path_buf()));
+        Self { inner, op, path }
+    }
+}
+
+impl<T: Read> Read for Metered<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        transfer(self.op, &self.path, bytes);
+        Ok(bytes)
+    }
+}
+
+impl<T: Seek> Seek for Metered<T> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<T: Write> Write for Metered<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes = self.inner.write(buf)?;
+        transfer(self.op, &self.path, bytes);
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-    // Only set if needed
-    match &**guard {
-        Vfs::Memfs(ref _vfs) => (),
-        _ => *guard = Arc::new(Vfs::memfs()),
+// Resolve the provider owning the given path along with the path to pass it
+//
+// Mounted providers are given the absolute path while the current provider is given the original
+// path untouched to avoid any overhead when nothing is mounted. The current provider is given the
+// absolute path as well when the working directory is tracked by the mounts.
+fn resolve<'a>(mounts: &'a Mounts, vfs: &'a Vfs, path: &'a Path) -> (&'a Vfs, Cow<'a, Path>) {
+    if !mounts.points.is_empty() {
+        if let Ok(abs) = mounts.abs(vfs, path) {
+            if let Some(mounted) = mounts.owner(&abs) {
+                return (mounted, Cow::Owned(abs));
+            }
+            if mounts.cwd.is_some() {
+                return (vfs, Cow::Owned(abs));
+            }
+        }
     }
+    (vfs, Cow::Borrowed(path))
+}
+
+// Returns true if the given path is a directory leading to a mount point
+fn leads_to_mount(path: &Path) -> bool {
+    with_mounts(|mounts, vfs| {
+        !mounts.points.is_empty() && mounts.abs(vfs, path).is_ok_and(|x| mounts.leads_to(&x))
+    })
+}
+
+// Fail when the given path is or contains a mount point as removing or moving it would orphan the
+// mounted provider.
+fn unmounted<T: AsRef<Path>>(path: T) -> RvResult<()> {
+    with_mounts(|mounts, vfs| {
+        if mounts.points.is_empty() {
+            return Ok(());
+        }
+        let abs = mounts.abs(vfs, path)?;
+        match mounts.points.iter().find(|(x, _)| x.starts_with(&abs)) {
+            Some((x, _)) => Err(CoreError::msg(format!("Path contains mount point: {}", x.display())).into()),
+            None => Ok(()),
+        }
+    })
+}
+
+// Call the given facade operation with the provider owning the given path
+fn route<T: AsRef<Path>, R: Outcome, F: FnOnce(&Vfs, &Path) -> R>(op: &'static str, path: T, f: F) -> R {
+    let path = path.as_ref();
+    call(op, &[path], || {
+        with_mounts(|mounts, vfs| {
+            let (vfs, path) = resolve(mounts, vfs, path);
+            f(vfs, &path)
+        })
+    })
+}
+
+// Call the given listing function with the provider owning the given path then merge in any
+// mount points and mounted content that fall below it.
+//
+// * `recurse` when the listing includes all descendants rather than just the direct children
+// * `dirs` when the listing includes directories
+fn list<T: AsRef<Path>>(
+    op: &'static str, path: T, recurse: bool, dirs: bool, f: fn(&Vfs, &Path) -> RvResult<Vec<PathBuf>>,
+) -> RvResult<Vec<PathBuf>> {
+    let path = path.as_ref();
+    call(op, &[path], || list_mounted(path, recurse, dirs, f))
+}
+
+// Merge the mounts below the given path into the listing as described by `list`
+fn list_mounted(
+    path: &Path, recurse: bool, dirs: bool, f: fn(&Vfs, &Path) -> RvResult<Vec<PathBuf>>,
+) -> RvResult<Vec<PathBuf>> {
+    with_mounts(|mounts, vfs| {
+        let (owner, path) = resolve(mounts, vfs, path);
+        if mounts.points.is_empty() {
+            return f(owner, &path);
+        }
 
+        // Directories leading to mount points need not exist in the provider owning them
+        let root = mounts.abs(vfs, &path)?;
+        let mut paths = match f(owner, &path) {
+            Err(_) if mounts.leads_to(&root) && !owner.exists(&root) => vec![],
+            paths => paths?,
+        };
+
+        // Only keep paths owned by the provider that listed them
+        let owns = |provider: &Vfs, x: &Path| std::ptr::eq(resolve(mounts, vfs, x).0, provider);
+        let mut merged = false;
+        for (point, mounted) in mounts.points.iter().filter(|(x, _)| x != &root && x.starts_with(&root)) {
+            if !merged {
+                paths.retain(|x| owns(owner, x));
+                merged = true;
+            }
+            if dirs {
+                let mut dir = root.clone();
+                for component in point.components().skip(root.components().count()) {
+                    dir.push(component);
+                    paths.push(dir.clone());
+                    if !recurse {
+                        break;
+                    }
+                }
+            }
+            if !recurse {
+                continue;
+            }
+            paths.extend(f(mounted, point)?.into_iter().filter(|x| owns(mounted, x)));
+        }
+        if merged {
+            paths.sort();
+            paths.dedup();
+        }
+        Ok(paths)
+    })
+}
+
+// Copy the given source from one provider to the destination on another provider following the
+// same semantics as `copy` i.e. copy into `dst` if it is an existing directory.
+fn copy_across(from: &Vfs, src: &Path, to: &Vfs, dst: &Path) -> RvResult<()> {
+    let src = from.abs(src)?;
+    let dst = to.abs(dst)?;
+    let dst = if to.is_dir(&dst) { dst.mash(src.base()?) } else { dst };
+    to.mkdir_p(dst.dir()?)?;
+
+    for entry in from.entries(&src)? {
+        let entry = entry?;
+        let path = dst.mash(entry.path().trim_prefix(&src));
+
+        // Recreate links rather than following them
+        if entry.is_symlink() {
+            to.symlink(&path, from.readlink(entry.path())?)?;
+        } else if entry.is_dir() {
+            to.mkdir_m(&path, entry.mode() & 0o7777)?;
+        } else {
+            let mut data = vec![];
+            from.read(entry.path())?.read_to_end(&mut data)?;
+            to.write_all(&path, data)?;
+            to.chmod(&path, entry.mode() & 0o7777)?;
+        }
+    }
     Ok(())
 }
 
-/// Switch the current vfs provider to Stdfs if not already
+/// Save the current Memfs provider to the given image file
 ///
-/// * Use `set` to simply replace the vfs provider without checks
+/// The whole tree is written to a single versioned image file on the real filesystem including
+/// file contents, modes, ownership, symlinks and the current working directory. Use `load_image`
+/// to restore it later e.g. to ship prebuilt test fixtures or checkpoint a long running simulation.
+///
+/// * Only the current provider is saved, mounted providers are not included
+///
+/// ### Errors
+/// * VfsError::WrongProvider when the current provider isn't a Memfs
 ///
 /// ### Examples
-/// ```ignore
+/// ```
 /// use rivia_vfs::prelude::*;
 ///
-/// assert!(vfs::set_stdfs().is_ok());
+/// let image = std::env::temp_dir().join(format!("rivia_vfs_doc_save_image_{}", std::process::id()));
+/// assert!(vfs::set_memfs().is_ok());
+/// assert_write_all!("/foo", "bar");
+/// assert!(vfs::save_image(&image).is_ok());
+/// assert!(vfs::set(vfs::load_image(&image).unwrap()).is_ok());
+/// assert_read_all!("/foo", "bar");
+/// assert!(std::fs::remove_file(&image).is_ok());
 /// ```
-pub fn set_stdfs() -> RvResult<()> {
-    // Take lock for whole context to avoid collisions
-    let mut guard = VFS.write().unwrap();
+pub fn save_image<T: AsRef<Path>>(path: T) -> RvResult<()> {
+    let data = with(|vfs| match vfs {
+        Vfs::Memfs(_) => encode_image(vfs),
+        _ => Err(VfsError::WrongProvider.into()),
+    })?;
+    Vfs::stdfs().write_all(path, data)
+}
 
-    // Only set if needed
-    match &**guard {
-        Vfs::Stdfs(ref _vfs) => (),
-        _ => *guard = Arc::new(Vfs::memfs()),
+/// Load a new Memfs provider from the given image file
+///
+/// The image file is read from the real filesystem and must have been created with `save_image`.
+/// The returned provider isn't installed, use `set`, `set_local`, `mount` or `register` to do so.
+///
+/// ### Errors
+/// * CoreError::Msg when the image is malformed or its version isn't supported
+///
+/// ### Examples
+/// ```
+/// use rivia_vfs::prelude::*;
+///
+/// let image = std::env::temp_dir().join(format!("rivia_vfs_doc_load_image_{}", std::process::id()));
+/// assert!(vfs::set_memfs().is_ok());
+/// assert_mkdir_p!("/foo/bar");
+/// assert!(vfs::set_cwd("/foo").is_ok());
+/// assert!(vfs::save_image(&image).is_ok());
+/// assert!(vfs::set(vfs::load_image(&image).unwrap()).is_ok());
+/// assert_eq!(vfs::cwd().unwrap(), PathBuf::from("/foo"));
+/// assert_is_dir!("/foo/bar");
+/// assert!(std::fs::remove_file(&image).is_ok());
+/// ```
+pub fn load_image<T: AsRef<Path>>(path: T) -> RvResult<Vfs> {
+    let mut data = vec![];
+    Vfs::stdfs().read(path)?.read_to_end(&mut data)?;
+    decode_image(&data)
+}
+
+// Image files start with the magic bytes followed by the little endian format version then the
+// cwd and each entry in walk order. Entries are a kind byte followed by the path and either the
+// mode, uid, gid and for files the contents, or for symlinks the target. Paths and contents are
+// prefixed with their little endian u64 length.
+const IMAGE_MAGIC: &[u8] = b"RVFSIMG\0";
+const IMAGE_VERSION: u32 = 1;
+const IMAGE_DIR: u8 = 0;
+const IMAGE_FILE: u8 = 1;
+const IMAGE_LINK: u8 = 2;
+
+// Serialize the given provider's tree into the image format
+fn encode_image(vfs: &Vfs) -> RvResult<Vec<u8>> {
+    let mut image = IMAGE_MAGIC.to_vec();
+    image.extend(IMAGE_VERSION.to_le_bytes());
+    encode_bytes(&mut image, vfs.cwd()?.as_os_str().as_bytes());
+
+    for entry in vfs.entries(vfs.root())? {
+        let entry = entry?;
+        let path = entry.path();
+
+        // Links have no mode or ownership of their own to record
+        if entry.is_symlink() {
+            image.push(IMAGE_LINK);
+            encode_bytes(&mut image, path.as_os_str().as_bytes());
+            encode_bytes(&mut image, vfs.readlink(path)?.as_os_str().as_bytes());
+            continue;
+        }
+        let (uid, gid) = vfs.owner(path)?;
+        image.push(if entry.is_dir() { IMAGE_DIR } else { IMAGE_FILE });
+        encode_bytes(&mut image, path.as_os_str().as_bytes());
+        image.extend((entry.mode() & 0o7777).to_le_bytes());
+        image.extend(uid.to_le_bytes());
+        image.extend(gid.to_le_bytes());
+        if entry.is_file() {
+            let mut data = vec![];
+            vfs.read(path)?.read_to_end(&mut data)?;
+            encode_bytes(&mut image, &data);
+        }
+    }
+    Ok(image)
+}
+
+fn encode_bytes(image: &mut Vec<u8>, data: &[u8]) {
+    image.extend((data.len() as u64).to_le_bytes());
+    image.extend(data);
+}
+
+// Deserialize the given image into a new Memfs
+fn decode_image(mut image: &[u8]) -> RvResult<Vfs> {
+    if decode_take(&mut image, IMAGE_MAGIC.len())? != IMAGE_MAGIC {
+        return Err(CoreError::msg("Invalid vfs image given: missing magic bytes").into());
     }
+    let version = decode_u32(&mut image)?;
+    if version != IMAGE_VERSION {
+        return Err(CoreError::msg(format!("Unsupported vfs image version given: {}", version)).into());
+    }
+    let cwd = decode_path(&mut image)?;
 
-    Ok(())
+    // Entries are stored in walk order which is arbitrary. Parents are created before their
+    // children so that the recursive chmod and chown of a directory don't clobber its children,
+    // and links last as Memfs only makes a directory link when the target directory exists.
+    let (mut entries, mut links) = (vec![], vec![]);
+    while !image.is_empty() {
+        let kind = decode_take(&mut image, 1)?[0];
+        let path = decode_path(&mut image)?;
+        if kind == IMAGE_LINK {
+            links.push((path, decode_path(&mut image)?));
+            continue;
+        }
+        let mode = decode_u32(&mut image)?;
+        let (uid, gid) = (decode_u32(&mut image)?, decode_u32(&mut image)?);
+        let data = match kind {
+            IMAGE_DIR => None,
+            IMAGE_FILE => Some(decode_bytes(&mut image)?),
+            _ => return Err(CoreError::msg(format!("Invalid vfs image given: unknown kind {}", kind)).into()),
+        };
+        entries.push((path, mode, uid, gid, data));
+    }
+    entries.sort_by(|x, y| x.0.cmp(&y.0));
+    links.sort();
+
+    let vfs = Vfs::memfs();
+    for (path, mode, uid, gid, data) in entries {
+        match data {
+            Some(data) => vfs.write_all(&path, data)?,
+            None => vfs.mkdir_p(&path).map(|_| ())?,
+        }
+        vfs.chmod(&path, mode)?;
+        vfs.chown(&path, uid, gid)?;
+    }
+    for (link, target) in links {
+        vfs.symlink(&link, target)?;
+    }
+    vfs.set_cwd(cwd)?;
+    Ok(vfs)
+}
+
+fn decode_take<'a>(image: &mut &'a [u8], len: usize) -> RvResult<&'a [u8]> {
+    if image.len() < len {
+        return Err(CoreError::msg("Invalid vfs image given: unexpected end of image").into());
+    }
+    let (data, rest) = image.split_at(len);
+    *image = rest;
+    Ok(data)
+}
+
+fn decode_u32(image: &mut &[u8]) -> RvResult<u32> {
+    let mut data = [0; 4];
+    data.copy_from_slice(decode_take(image, 4)?);
+    Ok(u32::from_le_bytes(data))
+}
+
+fn decode_bytes<'a>(image: &mut &'a [u8]) -> RvResult<&'a [u8]> {
+    let mut len = [0; 8];
+    len.copy_from_slice(decode_take(image, 8)?);
+    let len = usize::try_from(u64::from_le_bytes(len))
+        .map_err(|_| CoreError::msg("Invalid vfs image given: entry too large"))?;
+    decode_take(image, len)
+}
+
+fn decode_path(image: &mut &[u8]) -> RvResult<PathBuf> {
+    Ok(PathBuf::from(OsStr::from_bytes(decode_bytes(image)?)))
 }
 
 /// Return the path in an absolute clean form
@@ -166,7 +1872,8 @@ pub fn set_stdfs() -> RvResult<()> {
 /// assert_eq!(vfs::abs("~").unwrap(), PathBuf::from(&home));
 /// ```
 pub fn abs<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().abs(path)
+    let path = path.as_ref();
+    call("abs", &[path], || with_mounts(|mounts, vfs| mounts.abs(vfs, path)))
 }
 
 /// Returns all dirs for the given path recursively
@@ -187,7 +1894,7 @@ pub fn abs<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 /// assert_iter_eq(vfs::all_dirs(&tmpdir).unwrap(), vec![dir1, dir2]);
 /// ```
 pub fn all_dirs<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
-    VFS.read().unwrap().clone().all_dirs(path)
+    list("all_dirs", path, true, true, |vfs, path| vfs.all_dirs(path))
 }
 
 /// Returns all files for the given path recursively
@@ -211,7 +1918,7 @@ pub fn all_dirs<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
 /// assert_iter_eq(vfs::all_files(&tmpdir).unwrap(), vec![file2, file1]);
 /// ```
 pub fn all_files<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
-    VFS.read().unwrap().clone().all_files(path)
+    list("all_files", path, true, false, |vfs, path| vfs.all_files(path))
 }
 
 /// Returns all paths for the given path recursively
@@ -237,7 +1944,7 @@ pub fn all_files<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
 /// assert_iter_eq(vfs::all_paths(&tmpdir).unwrap(), vec![dir1, file2, file3, file1]);
 /// ```
 pub fn all_paths<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
-    VFS.read().unwrap().clone().all_paths(path)
+    list("all_paths", path, true, true, |vfs, path| vfs.all_paths(path))
 }
 
 /// Opens a file in append mode
@@ -265,7 +1972,9 @@ pub fn all_paths<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
 /// assert_read_all!(&file, "foobar123".to_string());
 /// ```
 pub fn append<T: AsRef<Path>>(path: T) -> RvResult<Box<dyn Write>> {
-    VFS.read().unwrap().clone().append(path)
+    let path = path.as_ref();
+    let writer = route("append", path, |vfs, path| vfs.append(path))?;
+    Ok(Box::new(Metered::new("append", path, writer)))
 }
 
 /// Append the given data to to the target file
@@ -291,7 +2000,10 @@ pub fn append<T: AsRef<Path>>(path: T) -> RvResult<Box<dyn Write>> {
 /// assert_read_all!(&file, "foobar 1foobar 2");
 /// ```
 pub fn append_all<T: AsRef<Path>, U: AsRef<[u8]>>(path: T, data: U) -> RvResult<()> {
-    VFS.read().unwrap().clone().append_all(path, data)
+    let (path, data) = (path.as_ref(), data.as_ref());
+    route("append_all", path, |vfs, path| vfs.append_all(path, data))?;
+    transfer("append_all", path, data.len());
+    Ok(())
 }
 
 /// Append the given line to to the target file including a newline
@@ -317,7 +2029,7 @@ pub fn append_all<T: AsRef<Path>, U: AsRef<[u8]>>(path: T, data: U) -> RvResult<
 /// assert_read_all!(&file, "foobar 1foobar 2\n");
 /// ```
 pub fn append_line<T: AsRef<Path>, U: AsRef<str>>(path: T, line: U) -> RvResult<()> {
-    VFS.read().unwrap().clone().append_line(path, line)
+    route("append_line", path, |vfs, path| vfs.append_line(path, line))
 }
 
 /// Append the given lines to to the target file including newlines
@@ -342,7 +2054,7 @@ pub fn append_line<T: AsRef<Path>, U: AsRef<str>>(path: T, line: U) -> RvResult<
 /// assert_read_all!(&file, "1\n2\n");
 /// ```
 pub fn append_lines<T: AsRef<Path>, U: AsRef<str>>(path: T, lines: &[U]) -> RvResult<()> {
-    VFS.read().unwrap().clone().append_lines(path, lines)
+    route("append_lines", path, |vfs, path| vfs.append_lines(path, lines))
 }
 
 /// Change all file/dir permissions recursivly to `mode`
@@ -366,7 +2078,7 @@ pub fn append_lines<T: AsRef<Path>, U: AsRef<str>>(path: T, lines: &[U]) -> RvRe
 /// assert_eq!(vfs::mode(&file).unwrap(), 0o100555);
 /// ```
 pub fn chmod<T: AsRef<Path>>(path: T, mode: u32) -> RvResult<()> {
-    VFS.read().unwrap().clone().chmod(path, mode)
+    route("chmod", path, |vfs, path| vfs.chmod(path, mode))
 }
 
 /// Returns a new [`Chmod`] builder for advanced chmod options
@@ -394,7 +2106,7 @@ pub fn chmod<T: AsRef<Path>>(path: T, mode: u32) -> RvResult<()> {
 /// assert_eq!(vfs::mode(&file).unwrap(), 0o100777);
 /// ```
 pub fn chmod_b<T: AsRef<Path>>(path: T) -> RvResult<Chmod> {
-    VFS.read().unwrap().clone().chmod_b(path)
+    route("chmod_b", path, |vfs, path| vfs.chmod_b(path))
 }
 
 /// Change the ownership of the path recursivly
@@ -413,7 +2125,7 @@ pub fn chmod_b<T: AsRef<Path>>(path: T) -> RvResult<Chmod> {
 /// assert_eq!(vfs::owner(&file1).unwrap(), (5, 7));
 /// ```
 pub fn chown<T: AsRef<Path>>(path: T, uid: u32, gid: u32) -> RvResult<()> {
-    VFS.read().unwrap().clone().chown(path, uid, gid)
+    route("chown", path, |vfs, path| vfs.chown(path, uid, gid))
 }
 
 /// Creates new [`Chown`] for use with the builder pattern
@@ -432,7 +2144,7 @@ pub fn chown<T: AsRef<Path>>(path: T, uid: u32, gid: u32) -> RvResult<()> {
 /// assert_eq!(vfs::owner(&file1).unwrap(), (5, 7));
 /// ```
 pub fn chown_b<T: AsRef<Path>>(path: T) -> RvResult<Chown> {
-    VFS.read().unwrap().clone().chown_b(path)
+    route("chown_b", path, |vfs, path| vfs.chown_b(path))
 }
 
 /// Returns the highest priority active configuration directory.
@@ -456,7 +2168,7 @@ pub fn chown_b<T: AsRef<Path>>(path: T) -> RvResult<Chown> {
 /// assert_eq!(config, "this is a test");
 /// ```
 pub fn config_dir<T: AsRef<str>>(config: T) -> Option<PathBuf> {
-    VFS.read().unwrap().clone().config_dir(config)
+    call("config_dir", &[], || with(|vfs| vfs.config_dir(config)))
 }
 
 /// Copies src to dst recursively
@@ -480,7 +2192,18 @@ pub fn config_dir<T: AsRef<str>>(config: T) -> Option<PathBuf> {
 /// assert_read_all!(&file2, "this is a test");
 /// ```
 pub fn copy<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<()> {
-    VFS.read().unwrap().clone().copy(src, dst)
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    call("copy", &[src, dst], || {
+        with_mounts(|mounts, vfs| {
+            let (from, src) = resolve(mounts, vfs, src);
+            let (to, dst) = resolve(mounts, vfs, dst);
+            if std::ptr::eq(from, to) {
+                from.copy(src, dst)
+            } else {
+                copy_across(from, &src, to, &dst)
+            }
+        })
+    })
 }
 
 /// Creates a new [`Copier`] for use with the builder pattern
@@ -492,6 +2215,9 @@ pub fn copy<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<()> {
 /// * Options for recursion, mode setting and following links
 /// * Execute by calling `exec`
 ///
+/// ### Errors
+/// * VfsError::WrongProvider when `src` and `dst` belong to different mounted providers
+///
 /// ### Examples
 /// ```
 /// use rivia_vfs::prelude::*;
@@ -504,7 +2230,18 @@ pub fn copy<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<()> {
 /// assert_read_all!(&file2, "this is a test");
 /// ```
 pub fn copy_b<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<Copier> {
-    VFS.read().unwrap().clone().copy_b(src, dst)
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    call("copy_b", &[src, dst], || {
+        with_mounts(|mounts, vfs| {
+            let (from, src) = resolve(mounts, vfs, src);
+            let (to, dst) = resolve(mounts, vfs, dst);
+            if std::ptr::eq(from, to) {
+                from.copy_b(src, dst)
+            } else {
+                Err(VfsError::WrongProvider.into())
+            }
+        })
+    })
 }
 
 /// Returns the current working directory
@@ -521,7 +2258,12 @@ pub fn copy_b<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<Copier
 /// assert_eq!(&vfs::cwd().unwrap(), &dir);
 /// ```
 pub fn cwd() -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().cwd()
+    call("cwd", &[], || {
+        with_mounts(|mounts, vfs| match &mounts.cwd {
+            Some(cwd) => Ok(cwd.clone()),
+            None => vfs.cwd(),
+        })
+    })
 }
 
 /// Returns all directories for the given path, sorted by name
@@ -545,7 +2287,7 @@ pub fn cwd() -> RvResult<PathBuf> {
 /// assert_iter_eq(vfs::dirs(&tmpdir).unwrap(), vec![dir1, dir2]);
 /// ```
 pub fn dirs<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
-    VFS.read().unwrap().clone().dirs(path)
+    list("dirs", path, false, true, |vfs, path| vfs.dirs(path))
 }
 
 /// Returns an iterator over the given path
@@ -566,7 +2308,7 @@ pub fn dirs<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
 /// assert_iter_eq(iter.map(|x| x.unwrap().path_buf()), vec![vfs::root(), dir, file]);
 /// ```
 pub fn entries<T: AsRef<Path>>(path: T) -> RvResult<Entries> {
-    VFS.read().unwrap().clone().entries(path)
+    route("entries", path, |vfs, path| vfs.entries(path))
 }
 
 /// Return a virtual filesystem entry for the given path
@@ -583,7 +2325,7 @@ pub fn entries<T: AsRef<Path>>(path: T) -> RvResult<Entries> {
 /// assert!(vfs::entry(&file).unwrap().is_file());
 /// ```
 pub fn entry<T: AsRef<Path>>(path: T) -> RvResult<VfsEntry> {
-    VFS.read().unwrap().clone().entry(path)
+    route("entry", path, |vfs, path| vfs.entry(path))
 }
 
 /// Returns true if the `path` exists
@@ -601,7 +2343,7 @@ pub fn entry<T: AsRef<Path>>(path: T) -> RvResult<VfsEntry> {
 /// assert_eq!(vfs::exists(&dir), true);
 /// ```
 pub fn exists<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().exists(path)
+    route("exists", path, |vfs, path| vfs.exists(path) || leads_to_mount(path))
 }
 
 /// Returns all files for the given path, sorted by name
@@ -625,7 +2367,7 @@ pub fn exists<T: AsRef<Path>>(path: T) -> bool {
 /// assert_iter_eq(vfs::files(&tmpdir).unwrap(), vec![file1, file2]);
 /// ```
 pub fn files<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
-    VFS.read().unwrap().clone().files(path)
+    list("files", path, false, false, |vfs, path| vfs.files(path))
 }
 
 /// Returns the group ID of the owner of this file
@@ -640,7 +2382,7 @@ pub fn files<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
 /// assert_eq!(vfs::gid(vfs::root()).unwrap(), 1000);
 /// ```
 pub fn gid<T: AsRef<Path>>(path: T) -> RvResult<u32> {
-    VFS.read().unwrap().clone().gid(path)
+    route("gid", path, |vfs, path| vfs.gid(path))
 }
 
 /// Returns true if the given path exists and is readonly
@@ -659,7 +2401,7 @@ pub fn gid<T: AsRef<Path>>(path: T) -> RvResult<u32> {
 /// assert_eq!(vfs::is_exec(&file), true);
 /// ```
 pub fn is_exec<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_exec(path)
+    route("is_exec", path, |vfs, path| vfs.is_exec(path))
 }
 
 /// Returns true if the given path exists and is a directory
@@ -678,7 +2420,7 @@ pub fn is_exec<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::is_dir(&dir), true);
 /// ```
 pub fn is_dir<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_dir(path)
+    route("is_dir", path, |vfs, path| vfs.is_dir(path) || leads_to_mount(path))
 }
 
 /// Returns true if the given path exists and is a file
@@ -697,7 +2439,7 @@ pub fn is_dir<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::is_file(&file), true);
 /// ```
 pub fn is_file<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_file(path)
+    route("is_file", path, |vfs, path| vfs.is_file(path))
 }
 
 /// Returns true if the given path exists and is readonly
@@ -717,7 +2459,7 @@ pub fn is_file<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::is_readonly(&file), true);
 /// ```
 pub fn is_readonly<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_readonly(path)
+    route("is_readonly", path, |vfs, path| vfs.is_readonly(path))
 }
 
 /// Returns true if the given path exists and is a symlink
@@ -736,7 +2478,7 @@ pub fn is_readonly<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::is_symlink(&link), true);
 /// ```
 pub fn is_symlink<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_symlink(path)
+    route("is_symlink", path, |vfs, path| vfs.is_symlink(path))
 }
 
 /// Returns true if the given path exists and is a symlink pointing to a directory
@@ -761,7 +2503,7 @@ pub fn is_symlink<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::is_symlink_dir(&link2), false);
 /// ```
 pub fn is_symlink_dir<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_symlink_dir(path)
+    route("is_symlink_dir", path, |vfs, path| vfs.is_symlink_dir(path))
 }
 
 /// Returns true if the given path exists and is a symlink pointing to a file
@@ -786,7 +2528,7 @@ pub fn is_symlink_dir<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::is_symlink_file(&link2), true);
 /// ```
 pub fn is_symlink_file<T: AsRef<Path>>(path: T) -> bool {
-    VFS.read().unwrap().clone().is_symlink_file(path)
+    route("is_symlink_file", path, |vfs, path| vfs.is_symlink_file(path))
 }
 
 /// Creates the given directory and any parent directories needed with the given mode
@@ -801,7 +2543,7 @@ pub fn is_symlink_file<T: AsRef<Path>>(path: T) -> bool {
 /// assert_eq!(vfs::mode(&dir).unwrap(), 0o40555);
 /// ```
 pub fn mkdir_m<T: AsRef<Path>>(path: T, mode: u32) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().mkdir_m(path, mode)
+    route("mkdir_m", path, |vfs, path| vfs.mkdir_m(path, mode))
 }
 
 /// Creates the given directory and any parent directories needed
@@ -822,7 +2564,7 @@ pub fn mkdir_m<T: AsRef<Path>>(path: T, mode: u32) -> RvResult<PathBuf> {
 /// assert_is_dir!(&dir);
 /// ```
 pub fn mkdir_p<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().mkdir_p(path)
+    route("mkdir_p", path, |vfs, path| vfs.mkdir_p(path))
 }
 
 /// Create an empty file similar to the linux touch command
@@ -846,7 +2588,7 @@ pub fn mkdir_p<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 /// assert_is_file!(&file);
 /// ```
 pub fn mkfile<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().mkfile(path)
+    route("mkfile", path, |vfs, path| vfs.mkfile(path))
 }
 
 /// Wraps `mkfile` allowing for setting the file's mode.
@@ -861,7 +2603,7 @@ pub fn mkfile<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 /// assert_eq!(vfs::mode(&file).unwrap(), 0o100555);
 /// ```
 pub fn mkfile_m<T: AsRef<Path>>(path: T, mode: u32) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().mkfile_m(path, mode)
+    route("mkfile_m", path, |vfs, path| vfs.mkfile_m(path, mode))
 }
 
 /// Returns the permissions for a file, directory or link
@@ -884,7 +2626,7 @@ pub fn mkfile_m<T: AsRef<Path>>(path: T, mode: u32) -> RvResult<PathBuf> {
 /// assert_eq!(vfs::mode(&file).unwrap(), 0o100555);
 /// ```
 pub fn mode<T: AsRef<Path>>(path: T) -> RvResult<u32> {
-    VFS.read().unwrap().clone().mode(path)
+    route("mode", path, |vfs, path| vfs.mode(path))
 }
 
 /// Move a file or directory
@@ -895,6 +2637,7 @@ pub fn mode<T: AsRef<Path>>(path: T) -> RvResult<u32> {
 ///
 /// ### Errors
 /// * PathError::DoesNotExist when the source doesn't exist
+/// * CoreError::Msg when the source is or contains a mount point
 ///
 /// ### Examples
 /// ```
@@ -911,7 +2654,20 @@ pub fn mode<T: AsRef<Path>>(path: T) -> RvResult<u32> {
 /// assert_is_file!(&dirfile);
 /// ```
 pub fn move_p<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<()> {
-    VFS.read().unwrap().clone().move_p(src, dst)
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    call("move_p", &[src, dst], || {
+        unmounted(src)?;
+        with_mounts(|mounts, vfs| {
+            let (from, src) = resolve(mounts, vfs, src);
+            let (to, dst) = resolve(mounts, vfs, dst);
+            if std::ptr::eq(from, to) {
+                from.move_p(src, dst)
+            } else {
+                copy_across(from, &src, to, &dst)?;
+                from.remove_all(src)
+            }
+        })
+    })
 }
 
 /// Returns the (user ID, group ID) of the owner of this file
@@ -926,7 +2682,7 @@ pub fn move_p<T: AsRef<Path>, U: AsRef<Path>>(src: T, dst: U) -> RvResult<()> {
 /// assert_eq!(vfs::owner(vfs::root()).unwrap(), (1000, 1000));
 /// ```
 pub fn owner<T: AsRef<Path>>(path: T) -> RvResult<(u32, u32)> {
-    VFS.read().unwrap().clone().owner(path)
+    route("owner", path, |vfs, path| vfs.owner(path))
 }
 
 /// Returns all paths for the given path, sorted by name
@@ -950,7 +2706,7 @@ pub fn owner<T: AsRef<Path>>(path: T) -> RvResult<(u32, u32)> {
 /// assert_iter_eq(vfs::paths(&tmpdir).unwrap(), vec![dir1, dir2, file1]);
 /// ```
 pub fn paths<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
-    VFS.read().unwrap().clone().paths(path)
+    list("paths", path, false, true, |vfs, path| vfs.paths(path))
 }
 
 /// Attempts to open a file in readonly mode
@@ -975,7 +2731,9 @@ pub fn paths<T: AsRef<Path>>(path: T) -> RvResult<Vec<PathBuf>> {
 /// assert_eq!(buf, "foobar 1".to_string());
 /// ```
 pub fn read<T: AsRef<Path>>(path: T) -> RvResult<Box<dyn ReadSeek>> {
-    VFS.read().unwrap().clone().read(path)
+    let path = path.as_ref();
+    let reader = route("read", path, |vfs, path| vfs.read(path))?;
+    Ok(Box::new(Metered::new("read", path, reader)))
 }
 
 /// Read all data from the given file and return it as a String
@@ -996,7 +2754,10 @@ pub fn read<T: AsRef<Path>>(path: T) -> RvResult<Box<dyn ReadSeek>> {
 /// assert_read_all!(&file, "foobar 1");
 /// ```
 pub fn read_all<T: AsRef<Path>>(path: T) -> RvResult<String> {
-    VFS.read().unwrap().clone().read_all(path)
+    let path = path.as_ref();
+    let data = route("read_all", path, |vfs, path| vfs.read_all(path))?;
+    transfer("read_all", path, data.len());
+    Ok(data)
 }
 
 /// Read the given file and returns it as lines in a vector
@@ -1017,7 +2778,7 @@ pub fn read_all<T: AsRef<Path>>(path: T) -> RvResult<String> {
 /// assert_eq!(vfs::read_lines(&file).unwrap(), vec!["1".to_string(), "2".to_string()]);
 /// ```
 pub fn read_lines<T: AsRef<Path>>(path: T) -> RvResult<Vec<String>> {
-    VFS.read().unwrap().clone().read_lines(path)
+    route("read_lines", path, |vfs, path| vfs.read_lines(path))
 }
 
 /// Returns the relative path of the target the link points to
@@ -1038,7 +2799,7 @@ pub fn read_lines<T: AsRef<Path>>(path: T) -> RvResult<Vec<String>> {
 /// assert_readlink!(&link, PathBuf::from("..").mash("file"));
 /// ```
 pub fn readlink<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().readlink(path)
+    route("readlink", path, |vfs, path| vfs.readlink(path))
 }
 
 /// Returns the absolute path of the target the link points to
@@ -1057,7 +2818,7 @@ pub fn readlink<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 /// assert_readlink_abs!(&link, &file);
 /// ```
 pub fn readlink_abs<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().readlink_abs(path)
+    route("readlink_abs", path, |vfs, path| vfs.readlink_abs(path))
 }
 
 /// Removes the given empty directory or file
@@ -1067,6 +2828,7 @@ pub fn readlink_abs<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 ///
 /// ### Errors
 /// * a directory containing files will trigger an error. use `remove_all` instead
+/// * CoreError::Msg when the path is or contains a mount point
 ///
 /// ### Examples
 /// ```
@@ -1080,7 +2842,10 @@ pub fn readlink_abs<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 /// assert_no_exists!(&file);
 /// ```
 pub fn remove<T: AsRef<Path>>(path: T) -> RvResult<()> {
-    VFS.read().unwrap().clone().remove(path)
+    route("remove", path, |vfs, path| {
+        unmounted(path)?;
+        vfs.remove(path)
+    })
 }
 
 /// Removes the given directory after removing all of its contents
@@ -1088,6 +2853,9 @@ pub fn remove<T: AsRef<Path>>(path: T) -> RvResult<()> {
 /// * Handles path expansion and absolute path resolution
 /// * Link exclusion i.e. removes the link themselves not what its points to
 ///
+/// ### Errors
+/// * CoreError::Msg when the path is or contains a mount point
+///
 /// ### Examples
 /// ```
 /// use rivia_vfs::prelude::*;
@@ -1103,7 +2871,10 @@ pub fn remove<T: AsRef<Path>>(path: T) -> RvResult<()> {
 /// assert_no_exists!(&dir);
 /// ```
 pub fn remove_all<T: AsRef<Path>>(path: T) -> RvResult<()> {
-    VFS.read().unwrap().clone().remove_all(path)
+    route("remove_all", path, |vfs, path| {
+        unmounted(path)?;
+        vfs.remove_all(path)
+    })
 }
 
 /// Returns the current root directory
@@ -1118,13 +2889,14 @@ pub fn remove_all<T: AsRef<Path>>(path: T) -> RvResult<()> {
 /// assert_eq!(vfs::root(), root);
 /// ```
 pub fn root() -> PathBuf {
-    VFS.read().unwrap().clone().root()
+    call("root", &[], || with(|vfs| vfs.root()))
 }
 
 /// Set the current working directory
 ///
 /// * Handles path expansion and absolute path resolution
 /// * Relative path will use the current working directory
+/// * Directories in mounted providers are tracked with the mounts, see `mount`
 ///
 /// ### Errors
 /// * PathError::DoesNotExist(PathBuf) when the given path doesn't exist
@@ -1141,7 +2913,26 @@ pub fn root() -> PathBuf {
 /// assert_eq!(vfs::cwd().unwrap(), dir);
 /// ```
 pub fn set_cwd<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().set_cwd(path)
+    let path = path.as_ref();
+    call("set_cwd", &[path], || {
+        let (path, cwd) = with_mounts(|mounts, vfs| -> RvResult<(PathBuf, Option<PathBuf>)> {
+            if mounts.points.is_empty() {
+                return Ok((vfs.set_cwd(path)?, None));
+            }
+            let abs = mounts.abs(vfs, path)?;
+            match mounts.owner(&abs) {
+                Some(owner) => Ok((owner.set_cwd(&abs)?, Some(abs))),
+                None if mounts.leads_to(&abs) && !vfs.is_dir(&abs) => Ok((abs.clone(), Some(abs))),
+                None => Ok((vfs.set_cwd(&abs)?, None)),
+            }
+        })?;
+
+        // Working directories outside of the current provider are tracked with the mounts
+        if cwd.is_some() || with_mounts(|mounts, _| mounts.cwd.is_some()) {
+            update_mounts(|mounts| mounts.cwd = cwd.clone());
+        }
+        Ok(path)
+    })
 }
 
 /// Creates a new symbolic link
@@ -1166,7 +2957,7 @@ pub fn set_cwd<T: AsRef<Path>>(path: T) -> RvResult<PathBuf> {
 /// assert_readlink_abs!(&link, &file);
 /// ```
 pub fn symlink<T: AsRef<Path>, U: AsRef<Path>>(link: T, target: U) -> RvResult<PathBuf> {
-    VFS.read().unwrap().clone().symlink(link, target)
+    route("symlink", link, |vfs, link| vfs.symlink(link, target))
 }
 
 /// Returns the user ID of the owner of this file
@@ -1181,7 +2972,7 @@ pub fn symlink<T: AsRef<Path>, U: AsRef<Path>>(link: T, target: U) -> RvResult<P
 /// assert_eq!(vfs::uid(vfs::root()).unwrap(), 1000);
 /// ```
 pub fn uid<T: AsRef<Path>>(path: T) -> RvResult<u32> {
-    VFS.read().unwrap().clone().uid(path)
+    route("uid", path, |vfs, path| vfs.uid(path))
 }
 
 /// Opens a file in write-only mode
@@ -1205,7 +2996,9 @@ pub fn uid<T: AsRef<Path>>(path: T) -> RvResult<u32> {
 /// assert_read_all!(&file, "foobar");
 /// ```
 pub fn write<T: AsRef<Path>>(path: T) -> RvResult<Box<dyn Write>> {
-    VFS.read().unwrap().clone().write(path)
+    let path = path.as_ref();
+    let writer = route("write", path, |vfs, path| vfs.write(path))?;
+    Ok(Box::new(Metered::new("write", path, writer)))
 }
 
 /// Write the given data to to the target file
@@ -1230,7 +3023,10 @@ pub fn write<T: AsRef<Path>>(path: T) -> RvResult<Box<dyn Write>> {
 /// assert_read_all!(&file, "foobar 1");
 /// ```
 pub fn write_all<T: AsRef<Path>, U: AsRef<[u8]>>(path: T, data: U) -> RvResult<()> {
-    VFS.read().unwrap().clone().write_all(path, data)
+    let (path, data) = (path.as_ref(), data.as_ref());
+    route("write_all", path, |vfs, path| vfs.write_all(path, data))?;
+    transfer("write_all", path, data.len());
+    Ok(())
 }
 
 /// Write the given lines to to the target file including final newline
@@ -1255,32 +3051,115 @@ pub fn write_all<T: AsRef<Path>, U: AsRef<[u8]>>(path: T, data: U) -> RvResult<(
 /// assert_read_all!(&file, "1\n2\n".to_string());
 /// ```
 pub fn write_lines<T: AsRef<Path>, U: AsRef<str>>(path: T, lines: &[U]) -> RvResult<()> {
-    VFS.read().unwrap().clone().write_lines(path, lines)
+    route("write_lines", path, |vfs, path| vfs.write_lines(path, lines))
 }
 
-// Unit tests
-// -------------------------------------------------------------------------------------------------
-#[cfg(test)]
-mod tests {
-    use crate::prelude::*;
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use crate::prelude::*;
+
+    #[test]
+    fn test_abs() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let home = sys::home_dir().unwrap();
+        assert_eq!(vfs::abs("~").unwrap(), PathBuf::from(&home));
+    }
+
+    #[test]
+    fn test_add_latency() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let dir = tmpdir.mash("dir");
+        let file1 = tmpdir.mash("file1");
+        let file2 = dir.mash("file2");
+        assert_mkdir_p!(&dir);
+        assert_write_all!(&file2, "foobar");
+
+        // Time never passes without any latency
+        assert_eq!(vfs::clock(), Duration::ZERO);
+        assert!(vfs::write_all(&file1, "foobar").is_ok());
+        assert_eq!(vfs::clock(), Duration::ZERO);
+
+        // Delay only the matching calls
+        assert!(vfs::add_latency(vfs::Latency::new("write_all").delay(Duration::from_millis(5))).is_ok());
+        assert!(vfs::write_all(&file1, "foobar").is_ok());
+        assert!(vfs::read_all(&file1).is_ok());
+        assert_eq!(vfs::clock(), Duration::from_millis(5));
+        let glob = dir.mash("*").to_string().unwrap();
+        let latency = vfs::Latency::new("read_all").glob(glob).delay(Duration::from_millis(10));
+        assert!(vfs::add_latency(latency).is_ok());
+        assert!(vfs::read_all(&file1).is_ok());
+        assert_eq!(vfs::clock(), Duration::from_millis(5));
+        assert!(vfs::read_all(&file2).is_ok());
+        assert_eq!(vfs::clock(), Duration::from_millis(15));
+
+        // Calls rejected in read-only mode take no time
+        assert!(vfs::set_readonly(true).is_ok());
+        assert!(vfs::write_all(&file1, "foobar").is_err());
+        assert_eq!(vfs::clock(), Duration::from_millis(15));
+        assert!(vfs::set_readonly(false).is_ok());
+
+        // Clearing leaves the clock as is until set
+        assert!(vfs::clear_latency().is_ok());
+        assert!(vfs::write_all(&file1, "foobar").is_ok());
+        assert_eq!(vfs::clock(), Duration::from_millis(15));
+        assert!(vfs::set_clock(Duration::ZERO).is_ok());
+        assert_eq!(vfs::clock(), Duration::ZERO);
+
+        // Limit the bandwidth of whole file calls
+        assert!(vfs::add_latency(vfs::Latency::new("write_all").bandwidth(1000)).is_ok());
+        assert!(vfs::write_all(&file1, vec![b'a'; 500]).is_ok());
+        assert_eq!(vfs::clock(), Duration::from_millis(500));
+        assert!(vfs::append_all(&file1, vec![b'a'; 500]).is_ok());
+        assert_eq!(vfs::clock(), Duration::from_millis(500));
+        assert!(vfs::add_latency(vfs::Latency::new("read_all").bandwidth(100)).is_ok());
+        assert_eq!(vfs::read_all(&file1).unwrap().len(), 1000);
+        assert_eq!(vfs::clock(), Duration::from_millis(10500));
+        assert!(vfs::clear_latency().is_ok());
+        assert!(vfs::set_clock(Duration::ZERO).is_ok());
+
+        // Limit the bandwidth of streamed data as it flows
+        assert!(vfs::add_latency(vfs::Latency::new("write").bandwidth(10)).is_ok());
+        let mut writer = vfs::write(&file1).unwrap();
+        assert_eq!(vfs::clock(), Duration::ZERO);
+        writer.write_all(b"0123456789").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(vfs::clock(), Duration::from_secs(1));
+        let latency = vfs::Latency::new("read").delay(Duration::from_millis(1)).bandwidth(5);
+        assert!(vfs::add_latency(latency).is_ok());
+        let mut buf = String::new();
+        vfs::read(&file1).unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "0123456789");
+        assert_eq!(vfs::clock(), Duration::from_millis(3001));
+        assert!(vfs::add_latency(vfs::Latency::new("append").bandwidth(1)).is_ok());
+        vfs::append(&file1).unwrap().write_all(b"ab").unwrap();
+        assert_eq!(vfs::clock(), Duration::from_millis(5001));
+        assert_read_all!(&file1, "0123456789ab");
 
-    #[test]
-    fn test_everything_sequentially() {
-        // test_abs
-        assert!(vfs::set_memfs().is_ok());
-        let home = sys::home_dir().unwrap();
-        assert_eq!(vfs::abs("~").unwrap(), PathBuf::from(&home));
+        // A new local override starts with a fresh clock
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert_eq!(vfs::clock(), Duration::ZERO);
+    }
 
-        //  fn test_all_dirs() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_all_dirs() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         let dir2 = dir1.mash("dir2");
         assert_mkdir_p!(&dir2);
         assert_iter_eq(vfs::all_dirs(&tmpdir).unwrap(), vec![dir1, dir2]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_all_files() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_all_files() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         let dir1 = tmpdir.mash("dir1");
         let file2 = dir1.mash("file2");
@@ -1289,9 +3168,12 @@ mod tests {
         assert_mkfile!(&file2);
         assert_iter_eq(vfs::all_files(&tmpdir).unwrap(), vec![file2, file1]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_all_paths() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_all_paths() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         let file1 = tmpdir.mash("file1");
         let file2 = dir1.mash("file2");
@@ -1302,9 +3184,12 @@ mod tests {
         assert_mkfile!(&file3);
         assert_iter_eq(vfs::all_paths(&tmpdir).unwrap(), vec![dir1, file2, file3, file1]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_append() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_append() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         let mut f = vfs::write(&file).unwrap();
         f.write_all(b"foobar").unwrap();
@@ -1314,9 +3199,12 @@ mod tests {
         f.flush().unwrap();
         assert_read_all!(&file, "foobar123".to_string());
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_append_all() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_append_all() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_no_file!(&file);
         assert!(vfs::append_all(&file, "foobar 1").is_ok());
@@ -1324,9 +3212,12 @@ mod tests {
         assert!(vfs::append_all(&file, "foobar 2").is_ok());
         assert_read_all!(&file, "foobar 1foobar 2");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_append_line() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_append_line() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_no_file!(&file);
         assert!(vfs::append_line(&file, "foobar 1").is_ok());
@@ -1334,9 +3225,12 @@ mod tests {
         assert!(vfs::append_line(&file, "foobar 2").is_ok());
         assert_read_all!(&file, "foobar 1\nfoobar 2\n");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_append_lines() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_append_lines() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_no_file!(&file);
         assert!(vfs::append_lines(&file, &["1", "2"]).is_ok());
@@ -1344,18 +3238,24 @@ mod tests {
         assert!(vfs::append_lines(&file, &["3"]).is_ok());
         assert_read_all!(&file, "1\n2\n3\n");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_chmod() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_chmod() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_mkfile!(&file);
         assert_eq!(vfs::mode(&file).unwrap(), 0o100644);
         assert!(vfs::chmod(&file, 0o555).is_ok());
         assert_eq!(vfs::mode(&file).unwrap(), 0o100555);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_chmod_b() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_chmod_b() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let file = dir.mash("file");
         assert_mkdir_p!(&dir);
@@ -1366,25 +3266,33 @@ mod tests {
         assert_eq!(vfs::mode(&dir).unwrap(), 0o40777);
         assert_eq!(vfs::mode(&file).unwrap(), 0o100777);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_chown() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_chown() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         assert_mkfile!(&file1);
         assert!(vfs::chown(&file1, 5, 7).is_ok());
         assert_eq!(vfs::owner(&file1).unwrap(), (5, 7));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_chown_b() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_chown_b() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         assert_mkfile!(&file1);
         assert!(vfs::chown_b(&file1).unwrap().owner(5, 7).exec().is_ok());
         assert_eq!(vfs::owner(&file1).unwrap(), (5, 7));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_config_dir() {
-        assert!(vfs::set_memfs().is_ok());
+    #[test]
+    fn test_config_dir() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         let dir = PathBuf::from("/etc/xdg");
         assert_mkdir_p!(&dir);
         let file1 = dir.mash("rivia.toml");
@@ -1395,31 +3303,42 @@ mod tests {
 
         let path = config_dir.mash("rivia.toml");
         assert_read_all!(&path, "this is a test");
+    }
 
-        //fn test_copy() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_copy() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         let file2 = tmpdir.mash("file2");
         assert_write_all!(&file1, "this is a test");
         assert!(vfs::copy(&file1, &file2).is_ok());
         assert_read_all!(&file2, "this is a test");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_copy_b() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_copy_b() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file1 = tmpdir.mash("file1");
         let file2 = tmpdir.mash("file2");
         assert_write_all!(&file1, "this is a test");
         assert!(vfs::copy_b(&file1, &file2).unwrap().exec().is_ok());
         assert_read_all!(&file2, "this is a test");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_cwd() {
-        assert!(vfs::set_memfs().is_ok());
+    #[test]
+    fn test_cwd() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         assert_eq!(vfs::cwd().unwrap(), vfs::root());
+    }
 
-        //fn test_dirs() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_dirs() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         let dir2 = tmpdir.mash("dir2");
         let file1 = tmpdir.mash("file1");
@@ -1428,9 +3347,12 @@ mod tests {
         assert_mkfile!(&file1);
         assert_iter_eq(vfs::dirs(&tmpdir).unwrap(), vec![dir1, dir2]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_entries() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_entries() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let file = dir.mash("file");
         assert_mkdir_p!(&dir);
@@ -1438,24 +3360,33 @@ mod tests {
         let iter = vfs::entries(&tmpdir).unwrap().into_iter();
         assert_iter_eq(iter.map(|x| x.unwrap().path_buf()), vec![tmpdir.clone(), dir, file]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_entry() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_entry() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_mkfile!(&file);
         assert!(vfs::entry(&file).unwrap().is_file());
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_exists() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_exists() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
-        assert_eq!(vfs::exists(&dir), false);
+        assert!(!vfs::exists(&dir));
         assert_mkdir_p!(&dir);
-        assert_eq!(vfs::exists(&dir), true);
+        assert!(vfs::exists(&dir));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_files() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_files() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         let file1 = tmpdir.mash("file1");
         let file2 = tmpdir.mash("file2");
@@ -1464,57 +3395,216 @@ mod tests {
         assert_mkfile!(&file2);
         assert_iter_eq(vfs::files(&tmpdir).unwrap(), vec![file1, file2]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_gid() {
-        assert!(vfs::set_memfs().is_ok());
+    #[test]
+    fn test_from_uri() {
+        assert_eq!(vfs::ProviderKind::from(&vfs::from_uri("memfs://").unwrap()), vfs::ProviderKind::Memfs);
+        assert_eq!(vfs::ProviderKind::from(&vfs::from_uri("memfs:///").unwrap()), vfs::ProviderKind::Memfs);
+        assert_eq!(vfs::ProviderKind::from(&vfs::from_uri("MEMFS://").unwrap()), vfs::ProviderKind::Memfs);
+        assert_eq!(vfs::ProviderKind::from(&vfs::from_uri("file://").unwrap()), vfs::ProviderKind::Stdfs);
+        assert_eq!(vfs::ProviderKind::from(&vfs::from_uri(" file:/// ").unwrap()), vfs::ProviderKind::Stdfs);
+
+        // Errors
+        assert_eq!(vfs::from_uri("memfs").unwrap_err().to_string(), "Invalid vfs uri given: memfs");
+        assert_eq!(vfs::from_uri("foo://").unwrap_err().to_string(), "Unsupported vfs uri scheme given: foo://");
+        assert_eq!(
+            vfs::from_uri("chroot:///srv/app").unwrap_err().to_string(),
+            "Unsupported vfs uri scheme given: chroot:///srv/app"
+        );
+        assert_eq!(
+            vfs::from_uri("file:///srv/app").unwrap_err().to_string(),
+            "Unsupported vfs uri path given: file:///srv/app"
+        );
+    }
+
+    #[test]
+    fn test_gid() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         assert_eq!(vfs::gid(vfs::root()).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_handle() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let file1 = tmpdir.mash("file1");
+        let file2 = tmpdir.mash("file2");
+        let handle = vfs::handle();
+        assert_eq!(handle.kind(), vfs::ProviderKind::Memfs);
+        assert!(handle.write_all(&file1, "this is a test").is_ok());
 
-        //fn test_is_exec() {
-        let tmpdir = assert_memfs_setup!();
+        // Operations continue against the captured provider after switching
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(handle.copy(&file1, &file2).is_ok());
+        assert_eq!(handle.read_all(&file2).unwrap(), "this is a test");
+        assert_vfs_is_file!(handle, &file2);
+        assert_no_file!(&file2);
+    }
+
+    #[test]
+    fn test_inject() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let dir = tmpdir.mash("dir");
+        let file1 = dir.mash("file1");
+        let file2 = dir.mash("file2.toml");
+        assert_mkdir_p!(&dir);
+        assert_write_all!(&file1, "foobar");
+        let kind = |x: RvResult<String>| match x.unwrap_err() {
+            RvError::Io(e) => e.kind(),
+            e => panic!("unexpected error: {}", e),
+        };
+
+        // Fail every matching call with the given kind before it reaches the provider
+        let fault = vfs::Fault::new("write_all").glob(tmpdir.mash("**").to_string().unwrap());
+        assert!(vfs::inject(fault.kind(io::ErrorKind::StorageFull)).is_ok());
+        let err = vfs::write_all(&file1, "barfoo").unwrap_err().to_string();
+        assert_eq!(err, format!("Injected fault for write_all: {} (no storage space)", file1.display()));
+        assert!(vfs::write_all(&file2, "barfoo").is_err());
+        assert!(vfs::write_all("/foo", "barfoo").is_ok());
+        assert!(vfs::append_all(&file1, "barfoo").is_ok());
+        assert_read_all!(&file1, "foobarbarfoo");
+        assert_no_file!(&file2);
+        assert!(vfs::clear_faults().is_ok());
+        assert!(vfs::write_all(&file2, "barfoo").is_ok());
+
+        // Globs match within components with `*` and `?`
+        assert!(vfs::inject(vfs::Fault::new("read_all").glob(dir.mash("*.toml").to_string().unwrap())).is_ok());
+        assert!(vfs::read_all(&file1).is_ok());
+        assert!(vfs::read_all(&file2).is_err());
+        assert!(vfs::clear_faults().is_ok());
+        let glob = tmpdir.mash("*/file?").to_string().unwrap();
+        assert!(vfs::inject(vfs::Fault::new("read_all").glob(glob)).is_ok());
+        assert!(vfs::read_all(&file1).is_err());
+        assert!(vfs::read_all(&file2).is_ok());
+        assert!(vfs::clear_faults().is_ok());
+        assert!(vfs::inject(vfs::Fault::new("read_all").glob(tmpdir.mash("*1").to_string().unwrap())).is_ok());
+        assert!(vfs::read_all(&file1).is_ok());
+        assert!(vfs::clear_faults().is_ok());
+
+        // Fire only on the Nth matching call
+        let fault = vfs::Fault::new("read_all").kind(io::ErrorKind::PermissionDenied).nth(2);
+        assert!(vfs::inject(fault).is_ok());
+        assert!(vfs::read_all(&file1).is_ok());
+        assert_eq!(kind(vfs::read_all(&file1)), io::ErrorKind::PermissionDenied);
+        assert!(vfs::read_all(&file1).is_ok());
+        assert!(vfs::clear_faults().is_ok());
+
+        // Fire with a seeded probability repeating the same failures for the same seed
+        let fires = |seed| {
+            assert!(vfs::inject(vfs::Fault::new("read_all").probability(0.5, seed)).is_ok());
+            let fires: Vec<bool> = (0..32).map(|_| vfs::read_all(&file1).is_err()).collect();
+            assert!(vfs::clear_faults().is_ok());
+            fires
+        };
+        let (fires1, fires2) = (fires(42), fires(42));
+        assert_eq!(fires1, fires2);
+        assert!(fires1.iter().any(|x| *x) && fires1.iter().any(|x| !*x));
+        assert_ne!(fires(7), fires1);
+        assert!(vfs::inject(vfs::Fault::new("read_all").probability(0.0, 42)).is_ok());
+        assert!((0..32).all(|_| vfs::read_all(&file1).is_ok()));
+        assert!(vfs::clear_faults().is_ok());
+
+        // Calls that can't fail are unaffected and failed calls are recorded
+        assert!(vfs::inject(vfs::Fault::new("exists")).is_ok());
+        assert!(vfs::inject(vfs::Fault::new("remove_all")).is_ok());
+        assert!(vfs::set_recording(true).is_ok());
+        assert!(vfs::exists(&file1));
+        assert!(vfs::remove_all(&dir).is_err());
+        assert_is_file!(&file1);
+        assert!(vfs::calls()[1].result.is_err());
+
+        // Other threads and new local overrides don't see the faults
+        std::thread::spawn(|| {
+            assert!(vfs::set_local(Vfs::memfs()).is_ok());
+            assert!(vfs::remove_all("/foo").is_ok());
+        })
+        .join()
+        .unwrap();
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(vfs::remove_all("/foo").is_ok());
+    }
+
+    #[test]
+    fn test_is_exec() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert!(vfs::mkfile_m(&file, 0o644).is_ok());
-        assert_eq!(vfs::is_exec(&file), false);
+        assert!(!vfs::is_exec(&file));
         assert!(vfs::chmod(&file, 0o777).is_ok());
-        assert_eq!(vfs::is_exec(&file), true);
+        assert!(vfs::is_exec(&file));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_is_dir() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_is_dir() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
-        assert_eq!(vfs::is_dir(&dir), false);
+        assert!(!vfs::is_dir(&dir));
         assert_mkdir_p!(&dir);
-        assert_eq!(vfs::is_dir(&dir), true);
+        assert!(vfs::is_dir(&dir));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_is_file() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_is_file() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
-        assert_eq!(vfs::is_file(&file), false);
+        assert!(!vfs::is_file(&file));
         assert_mkfile!(&file);
-        assert_eq!(vfs::is_file(&file), true);
+        assert!(vfs::is_file(&file));
         assert_remove_all!(&tmpdir);
+    }
+
+    #[test]
+    fn test_is_memfs() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(vfs::is_memfs());
+        assert!(vfs::set_local(Vfs::stdfs()).is_ok());
+        assert!(!vfs::is_memfs());
+    }
 
-        //fn test_is_readonly() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_is_readonly() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert!(vfs::mkfile_m(&file, 0o644).is_ok());
-        assert_eq!(vfs::is_readonly(&file), false);
+        assert!(!vfs::is_readonly(&file));
         assert!(vfs::chmod_b(&file).unwrap().readonly().exec().is_ok());
         assert_eq!(vfs::mode(&file).unwrap(), 0o100444);
-        assert_eq!(vfs::is_readonly(&file), true);
+        assert!(vfs::is_readonly(&file));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_is_symlink() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_is_stdfs() {
+        assert!(vfs::set_local(Vfs::stdfs()).is_ok());
+        assert!(vfs::is_stdfs());
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(!vfs::is_stdfs());
+    }
+
+    #[test]
+    fn test_is_symlink() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         let link = tmpdir.mash("link");
-        assert_eq!(vfs::is_symlink(&link), false);
+        assert!(!vfs::is_symlink(&link));
         assert_symlink!(&link, &file);
-        assert_eq!(vfs::is_symlink(&link), true);
+        assert!(vfs::is_symlink(&link));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_is_symlink_dir() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_is_symlink_dir() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let file = tmpdir.mash("file");
         let link1 = tmpdir.mash("link1");
@@ -1523,12 +3613,15 @@ mod tests {
         assert_mkfile!(&file);
         assert_symlink!(&link1, &dir);
         assert_symlink!(&link2, &file);
-        assert_eq!(vfs::is_symlink_dir(&link1), true);
-        assert_eq!(vfs::is_symlink_dir(&link2), false);
+        assert!(vfs::is_symlink_dir(&link1));
+        assert!(!vfs::is_symlink_dir(&link2));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_is_symlink_file() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_is_symlink_file() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let file = tmpdir.mash("file");
         let link1 = tmpdir.mash("link1");
@@ -1537,51 +3630,268 @@ mod tests {
         assert_mkfile!(&file);
         assert_symlink!(&link1, &dir);
         assert_symlink!(&link2, &file);
-        assert_eq!(vfs::is_symlink_file(&link1), false);
-        assert_eq!(vfs::is_symlink_file(&link2), true);
+        assert!(!vfs::is_symlink_file(&link1));
+        assert!(vfs::is_symlink_file(&link2));
         assert_remove_all!(&tmpdir);
+    }
+
+    #[test]
+    fn test_load_image() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let (stdfs, imgdir) = assert_vfs_setup!(Vfs::stdfs());
+        let image = imgdir.mash("image");
+        let dir = tmpdir.mash("dir");
+        let file = dir.mash("file");
+        let link = tmpdir.mash("link");
+        let dirlink = tmpdir.mash("a/dirlink");
+        assert_mkdir_p!(&dir);
+        assert_write_all!(&file, "file");
+        assert_symlink!(&link, &file);
+        assert_mkdir_p!(tmpdir.mash("a"));
+        assert_symlink!(&dirlink, &dir);
+        assert!(vfs::chmod(&dir, 0o555).is_ok());
+        assert!(vfs::chmod(&file, 0o600).is_ok());
+        assert!(vfs::chown(&file, 5, 7).is_ok());
+        assert!(vfs::set_cwd(&dir).is_ok());
+        assert!(vfs::save_image(&image).is_ok());
 
-        //fn test_mkdir_m() {
-        let tmpdir = assert_memfs_setup!();
+        // Contents, modes, ownership, links and cwd are restored into a new Memfs
+        let loaded = vfs::load_image(&image).unwrap();
+        assert!(matches!(loaded, Vfs::Memfs(_)));
+        assert_eq!(loaded.cwd().unwrap(), dir);
+        assert_vfs_read_all!(loaded, &file, "file");
+        assert_eq!(loaded.mode(&file).unwrap(), 0o100600);
+        assert_eq!(loaded.owner(&file).unwrap(), (5, 7));
+        assert_eq!(loaded.mode(&dir).unwrap(), 0o40555);
+        assert_vfs_readlink!(loaded, &link, PathBuf::from("dir/file"));
+        assert!(loaded.is_symlink_dir(&dirlink));
+        assert_eq!(loaded.all_paths(vfs::root()).unwrap(), vfs::all_paths(vfs::root()).unwrap());
+
+        // Links to directories stay directory links whatever order the entries are walked in
+        let mut loaded = loaded;
+        for _ in 0..20 {
+            assert!(vfs::set_local(loaded).is_ok());
+            assert!(vfs::save_image(&image).is_ok());
+            loaded = vfs::load_image(&image).unwrap();
+            assert!(loaded.is_symlink_dir(&dirlink));
+            assert!(loaded.is_symlink_file(&link));
+            assert_eq!(loaded.mode(&file).unwrap(), 0o100600);
+        }
+
+        // Corrupt, truncated or unsupported images are rejected
+        let mut data = vec![];
+        assert!(stdfs.read(&image).unwrap().read_to_end(&mut data).is_ok());
+        assert!(stdfs.write_all(&image, "foo").is_ok());
+        assert!(vfs::load_image(&image).is_err());
+        assert!(stdfs.write_all(&image, b"RVFSIMG\0\x02\0\0\0").is_ok());
+        assert_eq!(vfs::load_image(&image).unwrap_err().to_string(), "Unsupported vfs image version given: 2");
+        assert!(stdfs.write_all(&image, &data[..data.len() - 1]).is_ok());
+        assert!(vfs::load_image(&image).is_err());
+        assert!(stdfs.remove(&image).is_ok());
+        assert!(vfs::load_image(&image).is_err());
+        assert_vfs_remove_all!(stdfs, &imgdir);
+        assert_remove_all!(&tmpdir);
+    }
+
+    #[test]
+    fn test_mkdir_m() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         assert!(vfs::mkdir_m(&dir, 0o555).is_ok());
         assert_eq!(vfs::mode(&dir).unwrap(), 0o40555);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_mkdir_p() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_mkdir_p() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         assert_no_dir!(&dir);
         assert_eq!(&vfs::mkdir_p(&dir).unwrap(), &dir);
         assert_is_dir!(&dir);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_mkfile() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_mkfile() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_no_file!(&file);
         assert_eq!(&vfs::mkfile(&file).unwrap(), &file);
         assert_is_file!(&file);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_mkfile_m() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_mkfile_m() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert!(vfs::mkfile_m(&file, 0o555).is_ok());
         assert_eq!(vfs::mode(&file).unwrap(), 0o100555);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_mode() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_mode() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_mkfile!(&file);
         assert_eq!(vfs::mode(&file).unwrap(), 0o100644);
         assert!(vfs::chmod(&file, 0o555).is_ok());
         assert_eq!(vfs::mode(&file).unwrap(), 0o100555);
         assert_remove_all!(&tmpdir);
+    }
+
+    #[test]
+    fn test_mount() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let dir = tmpdir.mash("dir");
+        let mnt = tmpdir.mash("mnt");
+        let nested = mnt.mash("nested");
+        let file1 = dir.mash("file1");
+        let file2 = mnt.mash("file2");
+        let file3 = nested.mash("file3");
+        let new = tmpdir.mash("new");
+        let deep = new.mash("deep");
+        assert_mkdir_p!(&dir);
+        assert_write_all!(&file1, "file1");
+        assert!(vfs::mount(&mnt, Vfs::memfs()).is_ok());
+        assert!(vfs::mount(&nested, Vfs::memfs()).is_ok());
+        assert!(vfs::mount(&deep, Vfs::memfs()).is_ok());
+
+        // Mount points are only created in the mounted providers
+        assert_vfs_no_exists!(vfs::provider(), &mnt);
+        assert_vfs_no_exists!(vfs::provider(), &new);
+        assert_vfs_no_exists!(vfs::provider_at(&mnt), &nested);
+        assert_is_dir!(&mnt);
+        assert_is_dir!(&nested);
+        assert!(vfs::is_dir(&new));
+        assert!(vfs::exists(&new));
+        assert!(!vfs::is_dir(new.mash("other")));
+
+        // Mounting over Stdfs leaves the real filesystem untouched
+        std::thread::spawn(|| {
+            assert!(vfs::set_local(Vfs::stdfs()).is_ok());
+            let dir = std::env::temp_dir().mash(format!("rivia_vfs_test_mount_{}", std::process::id()));
+            assert!(vfs::mount(dir.mash("mnt"), Vfs::memfs()).is_ok());
+            assert_write_all!(dir.mash("mnt/file"), "scratch");
+            assert!(vfs::is_dir(&dir));
+            assert!(!Vfs::stdfs().exists(&dir));
+            assert!(vfs::umount(dir.mash("mnt")).is_some());
+            assert!(!Vfs::stdfs().exists(&dir));
+        })
+        .join()
+        .unwrap();
+
+        // Calls under the mount points are routed to the mounted providers
+        assert_write_all!(&file2, "file2");
+        assert_write_all!(&file3, "file3");
+        assert_vfs_no_file!(vfs::provider(), &file2);
+        assert_vfs_no_file!(vfs::provider(), &file3);
+        assert_vfs_no_file!(vfs::provider_at(&mnt), &file3);
+        assert_vfs_read_all!(vfs::provider_at(&nested), &file3, "file3");
+
+        // Mounts made under a local override only apply to the current thread
+        let file = file2.clone();
+        std::thread::spawn(move || {
+            assert!(vfs::set_local(Vfs::memfs()).is_ok());
+            assert!(Arc::ptr_eq(&vfs::provider_at(&file), &vfs::provider()));
+        })
+        .join()
+        .unwrap();
+
+        // Listings include mount points, the directories leading to them and mounted content
+        assert_iter_eq(vfs::dirs(&tmpdir).unwrap(), vec![dir.clone(), mnt.clone(), new.clone()]);
+        assert_iter_eq(vfs::paths(&mnt).unwrap(), vec![file2.clone(), nested.clone()]);
+        assert_iter_eq(vfs::files(&mnt).unwrap(), vec![file2.clone()]);
+        assert_iter_eq(vfs::dirs(&new).unwrap(), vec![deep.clone()]);
+        assert_iter_eq(vfs::all_dirs(&tmpdir).unwrap(), vec![
+            dir.clone(),
+            mnt.clone(),
+            nested.clone(),
+            new.clone(),
+            deep.clone(),
+        ]);
+        assert_iter_eq(vfs::all_files(&tmpdir).unwrap(), vec![file1.clone(), file2.clone(), file3.clone()]);
+        assert_iter_eq(vfs::all_paths(&tmpdir).unwrap(), vec![
+            dir.clone(),
+            file1.clone(),
+            mnt.clone(),
+            file2.clone(),
+            nested.clone(),
+            file3.clone(),
+            new.clone(),
+            deep.clone(),
+        ]);
+        assert!(vfs::dirs(new.mash("other")).is_err());
+
+        // Copy and move across mounts
+        assert!(vfs::copy(&file1, &mnt).is_ok());
+        assert_read_all!(mnt.mash("file1"), "file1");
+        assert!(vfs::copy(&dir, &nested).is_ok());
+        assert_read_all!(nested.mash("dir/file1"), "file1");
+        assert!(vfs::copy(&file1, mnt.mash("x/y/file1")).is_ok());
+        assert_read_all!(mnt.mash("x/y/file1"), "file1");
+        assert!(vfs::copy(&dir, nested.mash("x/y/dir")).is_ok());
+        assert_read_all!(nested.mash("x/y/dir/file1"), "file1");
+        assert!(vfs::copy_b(&file1, &mnt).is_err());
+        assert!(vfs::move_p(&file2, &dir).is_ok());
+        assert_no_file!(&file2);
+        assert_read_all!(dir.mash("file2"), "file2");
+
+        // The working directory can be in mounted providers and directories leading to them
+        assert_eq!(vfs::set_cwd(&mnt).unwrap(), mnt);
+        assert_eq!(vfs::cwd().unwrap(), mnt);
+        assert_eq!(vfs::read_all("file1").unwrap(), "file1");
+        assert_eq!(vfs::set_cwd("x").unwrap(), mnt.mash("x"));
+        assert_eq!(vfs::abs("y/file1").unwrap(), mnt.mash("x/y/file1"));
+        assert_eq!(vfs::read_all("y/file1").unwrap(), "file1");
+        assert!(vfs::set_cwd(mnt.mash("missing")).is_err());
+        assert_eq!(vfs::cwd().unwrap(), mnt.mash("x"));
+        assert_eq!(vfs::set_cwd(&new).unwrap(), new);
+        assert_iter_eq(vfs::dirs(".").unwrap(), vec![deep.clone()]);
+        assert_eq!(vfs::set_cwd("..").unwrap(), tmpdir);
+        assert_eq!(vfs::read_all("dir/file1").unwrap(), "file1");
+        assert_eq!(vfs::cwd().unwrap(), tmpdir);
+        assert_eq!(vfs::provider().cwd().unwrap(), tmpdir);
+
+        // The working directory falls back to the current provider's when its provider is unmounted
+        assert_eq!(vfs::set_cwd(&deep).unwrap(), deep);
+        assert!(vfs::umount(&deep).is_some());
+        assert_eq!(vfs::cwd().unwrap(), tmpdir);
+        assert!(!vfs::exists(&new));
+        assert!(vfs::set_cwd(vfs::root()).is_ok());
+
+        // Mount points can't be removed or moved while mounted
+        assert!(vfs::remove_all(&tmpdir).is_err());
+        assert!(vfs::remove_all(&mnt).is_err());
+        assert!(vfs::remove(&nested).is_err());
+        assert!(vfs::move_p(&mnt, &dir).is_err());
+        assert_is_file!(&file3);
+
+        // Mounting over an existing mount point replaces it
+        assert!(vfs::mount(&mnt, Vfs::memfs()).is_ok());
+        assert_no_file!(mnt.mash("file1"));
+        assert_is_file!(&file3);
+
+        assert!(vfs::umount(&nested).is_some());
+        assert!(vfs::umount(&mnt).is_some());
+        assert_no_exists!(&mnt);
+        assert_iter_eq(vfs::all_paths(&tmpdir).unwrap(), vec![dir.clone(), file1.clone(), dir.mash("file2")]);
+        assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_move_p() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_move_p() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let file = tmpdir.mash("file");
         let dirfile = dir.mash("file");
@@ -1591,13 +3901,30 @@ mod tests {
         assert_no_file!(&file);
         assert_is_file!(&dirfile);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_owner() {
-        assert!(vfs::set_memfs().is_ok());
+    #[test]
+    fn test_named() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(vfs::named("test_named").is_err());
+        assert!(vfs::register("test_named", Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!(@"test_named");
+        let file = tmpdir.mash("file");
+        assert!(vfs::named("test_named").unwrap().write_all(&file, "this is a test").is_ok());
+        assert_read_all!(@"test_named", &file, "this is a test");
+        assert_no_file!(&file);
+    }
+
+    #[test]
+    fn test_owner() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         assert_eq!(vfs::owner(vfs::root()).unwrap(), (1000, 1000));
+    }
 
-        //fn test_paths() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_paths() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir1 = tmpdir.mash("dir1");
         let dir2 = tmpdir.mash("dir2");
         let file1 = tmpdir.mash("file1");
@@ -1606,9 +3933,32 @@ mod tests {
         assert_mkfile!(&file1);
         assert_iter_eq(vfs::paths(&tmpdir).unwrap(), vec![dir1, dir2, file1]);
         assert_remove_all!(&tmpdir);
+    }
+
+    #[test]
+    fn test_provider() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let local = vfs::provider();
+        assert!(Arc::ptr_eq(&local, &vfs::provider()));
+        assert!(!Arc::ptr_eq(&local, &vfs::VFS.load()));
+        assert!(Arc::ptr_eq(&vfs::unset_local().unwrap(), &local));
+        assert!(vfs::unset_local().is_none());
+    }
 
-        //fn test_read() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_provider_kind() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert_eq!(vfs::provider_kind(), vfs::ProviderKind::Memfs);
+        assert_eq!(vfs::provider_kind().name(), "Memfs");
+        assert!(vfs::set_local(Vfs::stdfs()).is_ok());
+        assert_eq!(vfs::provider_kind(), vfs::ProviderKind::Stdfs);
+        assert_eq!(vfs::provider_kind().to_string(), "Stdfs");
+    }
+
+    #[test]
+    fn test_read() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_write_all!(&file, b"foobar 1");
         let mut file = vfs::read(&file).unwrap();
@@ -1616,24 +3966,33 @@ mod tests {
         assert!(file.read_to_string(&mut buf).is_ok());
         assert_eq!(buf, "foobar 1".to_string());
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_read_all() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_read_all() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_write_all!(&file, b"foobar 1");
         assert_read_all!(&file, "foobar 1");
         assert_eq!(vfs::read_all(&file).unwrap(), "foobar 1".to_string());
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_read_lines() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_read_lines() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_write_all!(&file, "1\n2");
         assert_eq!(vfs::read_lines(&file).unwrap(), vec!["1".to_string(), "2".to_string()]);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_readlink() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_readlink() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let dir = tmpdir.mash("dir");
         let link = dir.mash("link");
         let file = tmpdir.mash("file");
@@ -1642,77 +4001,448 @@ mod tests {
         assert_symlink!(&link, &file);
         assert_eq!(vfs::readlink(&link).unwrap(), PathBuf::from("..").mash("file"));
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_readlink_abs() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_readlink_abs() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         let link = tmpdir.mash("link");
         assert_mkfile!(&file);
         assert_symlink!(&link, &file);
         assert_eq!(vfs::readlink_abs(&link).unwrap(), file);
         assert_remove_all!(&tmpdir);
+    }
+
+    #[test]
+    fn test_register() {
+        assert!(vfs::register("test_register", Vfs::memfs()).is_ok());
+        let first = vfs::named("test_register").unwrap();
+        assert!(first.mkfile("file").is_ok());
+        assert_is_file!(@"test_register", "file");
+
+        // Registering the same name replaces the provider
+        assert!(vfs::register("test_register", Vfs::memfs()).is_ok());
+        assert_no_file!(@"test_register", "file");
+        assert_vfs_is_file!(first, "file");
+    }
 
-        //fn test_remove() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_remove() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_mkfile!(&file);
         assert_exists!(&file);
         assert!(vfs::remove(&file).is_ok());
         assert_no_exists!(&file);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_remove_all() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_remove_all() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_mkfile!(&file);
         assert_is_file!(&file);
         assert!(vfs::remove_all(&tmpdir).is_ok());
         assert_no_exists!(&file);
         assert_no_exists!(&tmpdir);
+    }
 
-        //fn test_root() {
-        assert!(vfs::set_memfs().is_ok());
+    #[test]
+    fn test_root() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         let mut root = PathBuf::new();
         root.push(Component::RootDir);
         assert_eq!(vfs::root(), root);
+    }
+
+    #[test]
+    fn test_save_image() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let (stdfs, imgdir) = assert_vfs_setup!(Vfs::stdfs());
+        let image = imgdir.mash("image");
+        let file = tmpdir.mash("file");
+        assert_write_all!(&file, "file");
+        assert!(vfs::save_image(&image).is_ok());
+        let mut data = vec![];
+        assert!(stdfs.read(&image).unwrap().read_to_end(&mut data).is_ok());
+        assert!(data.starts_with(b"RVFSIMG"));
+        assert_remove_all!(&tmpdir);
+
+        // Only Memfs can be saved
+        assert!(vfs::set_local(Vfs::stdfs()).is_ok());
+        assert!(vfs::save_image(imgdir.mash("stdfs")).is_err());
+        assert_vfs_no_exists!(stdfs, imgdir.mash("stdfs"));
+        assert_vfs_remove_all!(stdfs, &imgdir);
+    }
+
+    #[test]
+    fn test_set() {
+        // All switching of the global provider is done here to avoid racing other tests
+        vfs::unset_local();
+        let _guard = vfs::scoped(Vfs::stdfs());
+        let kind = || vfs::ProviderKind::from(&**vfs::VFS.load());
+
+        // provider falls back to the global without a local override
+        assert!(Arc::ptr_eq(&vfs::provider(), &vfs::VFS.load()));
+
+        // set installs exactly what it was given
+        assert!(vfs::set(Vfs::memfs()).is_ok());
+        assert_eq!(kind(), vfs::ProviderKind::Memfs);
+        assert!(vfs::set(Vfs::stdfs()).is_ok());
+        assert_eq!(kind(), vfs::ProviderKind::Stdfs);
+
+        // set_memfs installs Memfs and leaves an existing Memfs alone
+        assert!(vfs::set_memfs().is_ok());
+        assert_eq!(kind(), vfs::ProviderKind::Memfs);
+        let memfs = vfs::VFS.load_full();
+        assert!(vfs::set_memfs().is_ok());
+        assert!(Arc::ptr_eq(&memfs, &vfs::VFS.load()));
+
+        // set_stdfs installs Stdfs and leaves an existing Stdfs alone
+        assert!(vfs::set_stdfs().is_ok());
+        assert_eq!(kind(), vfs::ProviderKind::Stdfs);
+        let stdfs = vfs::VFS.load_full();
+        assert!(vfs::set_stdfs().is_ok());
+        assert!(Arc::ptr_eq(&stdfs, &vfs::VFS.load()));
+
+        // replace installs what it was given and returns the previous provider
+        let prev = vfs::replace(Vfs::memfs());
+        assert!(Arc::ptr_eq(&prev, &stdfs));
+        assert_eq!(kind(), vfs::ProviderKind::Memfs);
+
+        // scoped restores in LIFO order
+        let current = vfs::VFS.load_full();
+        {
+            let _outer = vfs::scoped(Vfs::stdfs());
+            assert_eq!(kind(), vfs::ProviderKind::Stdfs);
+            {
+                let _inner = vfs::scoped(Vfs::memfs());
+                assert_eq!(kind(), vfs::ProviderKind::Memfs);
+            }
+            assert_eq!(kind(), vfs::ProviderKind::Stdfs);
+        }
+        assert!(Arc::ptr_eq(&current, &vfs::VFS.load()));
 
-        //fn test_set_cwd() {
+        // Mounts made without a local override apply to all threads
+        assert!(vfs::mount("/mnt", Vfs::memfs()).is_ok());
+        let mounted = vfs::provider_at("/mnt");
+        assert!(!Arc::ptr_eq(&mounted, &vfs::VFS.load()));
+        std::thread::spawn(move || assert!(Arc::ptr_eq(&vfs::provider_at("/mnt"), &mounted))).join().unwrap();
+        assert!(vfs::umount("/mnt").is_some());
+
+        // Read-only mode set without a local override applies to all threads
+        assert!(vfs::set_readonly(true).is_ok());
+        assert!(vfs::mkdir_p("/foo").is_err());
+        std::thread::spawn(|| assert!(vfs::mkdir_p("/foo").is_err())).join().unwrap();
+        assert!(vfs::set_readonly(false).is_ok());
+        assert!(vfs::mkdir_p("/foo").is_ok());
+
+        // Recording started without a local override logs calls from all threads
+        assert!(vfs::set_recording(true).is_ok());
+        std::thread::spawn(|| assert!(vfs::is_dir("/foo"))).join().unwrap();
+        assert_called!(is_dir, "/foo");
+        assert!(vfs::set_recording(false).is_ok());
+
+        // Faults injected without a local override apply to all threads
+        assert!(vfs::inject(vfs::Fault::new("mkdir_p")).is_ok());
+        std::thread::spawn(|| assert!(vfs::mkdir_p("/foo").is_err())).join().unwrap();
+        assert!(vfs::clear_faults().is_ok());
+        assert!(vfs::mkdir_p("/foo").is_ok());
+
+        // Latency added without a local override advances the one clock from all threads
+        assert!(vfs::add_latency(vfs::Latency::new("is_dir").delay(Duration::from_secs(1))).is_ok());
+        std::thread::spawn(|| assert!(vfs::is_dir("/foo"))).join().unwrap();
+        assert!(vfs::is_dir("/foo"));
+        assert_eq!(vfs::clock(), Duration::from_secs(2));
+        assert!(vfs::clear_latency().is_ok());
+        assert!(vfs::set_clock(Duration::ZERO).is_ok());
+
+        // init_from_env installs the provider from the environment surfacing invalid values
+        assert!(vfs::set_stdfs().is_ok());
+        std::env::set_var(vfs::VFS_ENV, "memfs://");
+        assert!(vfs::init_from_env().is_ok());
+        assert_eq!(kind(), vfs::ProviderKind::Memfs);
+        std::env::set_var(vfs::VFS_ENV, "foo://");
+        assert_eq!(vfs::init_from_env().unwrap_err().to_string(), "Unsupported vfs uri scheme given: foo://");
+        assert_eq!(kind(), vfs::ProviderKind::Memfs);
+        std::env::remove_var(vfs::VFS_ENV);
+        assert!(vfs::init_from_env().is_ok());
+        assert_eq!(kind(), vfs::ProviderKind::Memfs);
+    }
+
+    #[test]
+    fn test_set_cwd() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         assert_eq!(vfs::cwd().unwrap(), vfs::root());
         assert!(vfs::set_cwd(vfs::root()).is_ok());
+    }
+
+    #[test]
+    fn test_set_local() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let file = tmpdir.mash("file");
+        assert_mkfile!(&file);
+
+        // Other threads don't see this thread's provider
+        let file2 = file.clone();
+        std::thread::spawn(move || {
+            assert!(vfs::set_local(Vfs::memfs()).is_ok());
+            assert_no_file!(&file2);
+        })
+        .join()
+        .unwrap();
+        assert_is_file!(&file);
+
+        // Replacing the local provider gives a fresh filesystem
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert_no_file!(&file);
+
+        // set_memfs and set_stdfs switch the local provider rather than the global
+        assert!(vfs::set_local(Vfs::stdfs()).is_ok());
+        assert!(vfs::set_memfs().is_ok());
+        assert!(vfs::is_memfs());
+        let memfs = vfs::provider();
+        assert!(vfs::set_memfs().is_ok());
+        assert!(Arc::ptr_eq(&memfs, &vfs::provider()));
+        assert!(vfs::set_stdfs().is_ok());
+        assert!(vfs::is_stdfs());
+        assert!(vfs::set_memfs().is_ok());
+
+        // set, replace and scoped switch the local provider rather than the global
+        assert!(vfs::set(Vfs::stdfs()).is_ok());
+        assert!(vfs::is_stdfs());
+        let stdfs = vfs::provider();
+        let prev = vfs::replace(Vfs::memfs());
+        assert!(Arc::ptr_eq(&prev, &stdfs));
+        assert!(vfs::is_memfs());
+        let memfs = vfs::provider();
+        {
+            let _outer = vfs::scoped(Vfs::stdfs());
+            assert!(vfs::is_stdfs());
+            {
+                let _inner = vfs::scoped(Vfs::memfs());
+                assert!(vfs::is_memfs());
+                assert!(!Arc::ptr_eq(&memfs, &vfs::provider()));
+            }
+            assert!(vfs::is_stdfs());
+        }
+        assert!(Arc::ptr_eq(&memfs, &vfs::provider()));
+        let result = std::panic::catch_unwind(|| {
+            let _guard = vfs::scoped(Vfs::stdfs());
+            panic!("unwinding");
+        });
+        assert!(result.is_err());
+        assert!(Arc::ptr_eq(&memfs, &vfs::provider()));
+    }
+
+    #[test]
+    fn test_set_readonly() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let dir = tmpdir.mash("dir");
+        let file = tmpdir.mash("file");
+        let mnt = tmpdir.mash("mnt");
+        assert_write_all!(&file, "foobar");
+        assert!(vfs::mount(&mnt, Vfs::memfs()).is_ok());
+        assert!(!vfs::readonly());
+        assert!(vfs::set_readonly(true).is_ok());
+        assert!(vfs::readonly());
+
+        // Mutating calls are rejected with a read-only error before reaching any provider
+        let kind = |x: RvResult<()>| match x.unwrap_err() {
+            RvError::Io(e) => e.kind(),
+            e => panic!("unexpected error: {}", e),
+        };
+        assert_eq!(kind(vfs::write_all(&file, "barfoo")), io::ErrorKind::ReadOnlyFilesystem);
+        let msg = format!("Read-only vfs rejected write_all: {}", file.display());
+        assert_eq!(vfs::write_all(&file, "barfoo").unwrap_err().to_string(), msg);
+        assert_eq!(kind(vfs::append_line(&file, "barfoo")), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::chmod(&file, 0o600)), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::copy(&file, &dir)), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::move_p(&file, &dir)), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::remove_all(&file)), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::mkdir_p(&dir).map(|_| ())), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::mkfile(mnt.mash("file")).map(|_| ())), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::set_cwd(&tmpdir).map(|_| ())), io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(kind(vfs::mount(tmpdir.mash("new/mnt"), Vfs::memfs())), io::ErrorKind::ReadOnlyFilesystem);
+        assert!(vfs::umount(&mnt).is_none());
+        assert!(!vfs::exists(tmpdir.mash("new")));
+        assert_is_dir!(&mnt);
+        assert!(vfs::write(&file).is_err());
+        assert!(vfs::symlink(tmpdir.mash("link"), &file).is_err());
+        assert_no_dir!(&dir);
+        assert_no_file!(mnt.mash("file"));
+        assert_read_all!(&file, "foobar");
+
+        // Reading calls are unaffected
+        assert_eq!(vfs::read_all(&file).unwrap(), "foobar");
+        assert_eq!(vfs::all_paths(&tmpdir).unwrap(), vec![file.clone(), mnt.clone()]);
+        assert!(vfs::exists(&file));
+
+        // Other threads and new local overrides aren't read-only
+        std::thread::spawn(|| {
+            assert!(vfs::set_local(Vfs::memfs()).is_ok());
+            assert!(!vfs::readonly());
+        })
+        .join()
+        .unwrap();
+        assert!(vfs::set_readonly(false).is_ok());
+        assert!(vfs::write_all(&file, "barfoo").is_ok());
+        assert_read_all!(&file, "barfoo");
+        assert!(vfs::set_readonly(true).is_ok());
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(!vfs::readonly());
+    }
+
+    #[test]
+    fn test_set_recording() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let dir = tmpdir.mash("dir");
+        let file = tmpdir.mash("file");
+        let mnt = tmpdir.mash("mnt");
+        assert!(vfs::mount(&mnt, Vfs::memfs()).is_ok());
+        assert!(vfs::calls().is_empty());
+        assert!(vfs::set_recording(true).is_ok());
+
+        // Calls are logged with absolute path arguments and their results
+        assert!(vfs::set_cwd(&tmpdir).is_ok());
+        assert!(vfs::write_all("file", "foobar").is_ok());
+        assert!(vfs::copy(&file, mnt.mash("file")).is_ok());
+        assert!(vfs::read_all(&dir).is_err());
+        assert!(!vfs::exists(&dir));
+        let err = vfs::read_all(&dir).unwrap_err().to_string();
+        assert_eq!(vfs::calls(), vec![
+            vfs::Call { op: "set_cwd", args: vec![tmpdir.clone()], result: Ok(()) },
+            vfs::Call { op: "write_all", args: vec![file.clone()], result: Ok(()) },
+            vfs::Call { op: "copy", args: vec![file.clone(), mnt.mash("file")], result: Ok(()) },
+            vfs::Call { op: "read_all", args: vec![dir.clone()], result: Err(err.clone()) },
+            vfs::Call { op: "exists", args: vec![dir.clone()], result: Ok(()) },
+            vfs::Call { op: "read_all", args: vec![dir.clone()], result: Err(err) },
+        ]);
+        assert_called!(copy, mnt.mash("file"));
+        assert_not_called!(remove);
+
+        // Calls rejected by the read-only mode are logged as well
+        assert!(vfs::set_readonly(true).is_ok());
+        assert!(vfs::remove(&file).is_err());
+        assert!(vfs::set_readonly(false).is_ok());
+        assert_called!(remove, &file);
+        assert!(vfs::calls().last().unwrap().result.is_err());
+
+        // Restarting clears the log and stopping drops it
+        assert!(vfs::set_recording(true).is_ok());
+        assert!(vfs::calls().is_empty());
+        assert!(vfs::mkdir_p(&dir).is_ok());
+        assert_eq!(vfs::calls().len(), 1);
+        assert!(vfs::set_recording(false).is_ok());
+        assert!(vfs::mkdir_p(&dir).is_ok());
+        assert!(vfs::calls().is_empty());
 
-        //fn test_symlink() {
-        let tmpdir = assert_memfs_setup!();
+        // Other threads and new local overrides aren't recorded
+        assert!(vfs::set_recording(true).is_ok());
+        std::thread::spawn(|| {
+            assert!(vfs::set_local(Vfs::memfs()).is_ok());
+            assert!(vfs::mkdir_p("foo").is_ok());
+            assert!(vfs::calls().is_empty());
+        })
+        .join()
+        .unwrap();
+        assert!(vfs::calls().is_empty());
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(vfs::mkdir_p("foo").is_ok());
+        assert!(vfs::calls().is_empty());
+    }
+
+    #[test]
+    fn test_symlink() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         let link = tmpdir.mash("link");
         assert_mkfile!(&file);
         assert!(vfs::symlink(&link, &file).is_ok());
         assert_readlink_abs!(&link, &file);
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_uid() {
-        assert!(vfs::set_memfs().is_ok());
+    #[test]
+    fn test_uid() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
         assert_eq!(vfs::uid(vfs::root()).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_umount() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
+        let mnt = tmpdir.mash("mnt");
+        let file = mnt.mash("file");
+        assert!(vfs::umount(&mnt).is_none());
+        assert!(vfs::mount(&mnt, Vfs::memfs()).is_ok());
+        assert_mkfile!(&file);
+        let mounted = vfs::umount(&mnt).unwrap();
+        assert_vfs_is_file!(mounted, &file);
+        assert_no_file!(&file);
+        assert!(vfs::umount(&mnt).is_none());
+
+        // Replacing the local override drops its mounts
+        assert!(vfs::mount(&mnt, Vfs::memfs()).is_ok());
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(vfs::umount(&mnt).is_none());
+    }
+
+    #[test]
+    fn test_unregister() {
+        assert!(vfs::unregister("test_unregister").is_none());
+        assert!(vfs::register("test_unregister", Vfs::memfs()).is_ok());
+        let handle = vfs::unregister("test_unregister").unwrap();
+        assert_eq!(handle.kind(), vfs::ProviderKind::Memfs);
+        assert!(vfs::named("test_unregister").is_err());
+    }
 
-        //fn test_write() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_unset_local() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        assert!(vfs::unset_local().is_some());
+        assert!(vfs::unset_local().is_none());
+    }
+
+    #[test]
+    fn test_write() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         let mut f = vfs::write(&file).unwrap();
         f.write_all(b"foobar").unwrap();
         f.flush().unwrap();
         assert_read_all!(&file, "foobar");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_write_all() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_write_all() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_no_file!(&file);
         assert!(vfs::write_all(&file, "foobar 1").is_ok());
         assert_is_file!(&file);
         assert_read_all!(&file, "foobar 1");
         assert_remove_all!(&tmpdir);
+    }
 
-        //fn test_write_lines() {
-        let tmpdir = assert_memfs_setup!();
+    #[test]
+    fn test_write_lines() {
+        assert!(vfs::set_local(Vfs::memfs()).is_ok());
+        let tmpdir = assert_setup!();
         let file = tmpdir.mash("file");
         assert_no_file!(&file);
         assert!(vfs::write_lines(&file, &["1", "2"]).is_ok());